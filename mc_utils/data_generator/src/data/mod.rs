@@ -1,11 +1,17 @@
 use std::fs::File;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+use server::JarVersion;
 
 pub mod blocks;
 
 #[derive(Debug)]
 pub struct GeneratedData {
     pub blocks: blocks::Blocks,
+    /// The version of the server which generated the reports, if known
+    pub version: Option<JarVersion>,
+    /// The working directory of the run, if the [`Retention`](crate::Retention) kept it
+    pub working_dir: Option<PathBuf>,
 }
 
 impl GeneratedData {
@@ -13,6 +19,10 @@ impl GeneratedData {
         let blocks_file = File::open(dir.as_ref().join("blocks.json"))?;
         let blocks = serde_json::from_reader(blocks_file)
             .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
-        Ok(GeneratedData { blocks })
+        Ok(GeneratedData {
+            blocks,
+            version: None,
+            working_dir: None,
+        })
     }
 }
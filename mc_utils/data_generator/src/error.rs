@@ -0,0 +1,55 @@
+use std::{fmt::Display, path::PathBuf};
+
+use server::DownloadError;
+
+#[derive(Debug)]
+pub enum GeneratorError {
+    /// The working directory could not be set up or cleaned up
+    IoError(std::io::Error),
+    /// The version json or the server jar could not be downloaded
+    DownloadError(DownloadError),
+    /// Java is missing or the server did not exit successfully
+    JavaError(std::io::Error),
+    /// The generated reports could not be read
+    ParseError(std::io::Error),
+    /// The run failed with 'error' and its working directory was kept at 'dir'
+    KeptWorkingDir {
+        dir: PathBuf,
+        error: Box<GeneratorError>,
+    },
+}
+
+impl From<DownloadError> for GeneratorError {
+    fn from(error: DownloadError) -> Self {
+        GeneratorError::DownloadError(error)
+    }
+}
+
+impl Display for GeneratorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GeneratorError::IoError(error) => error.fmt(f),
+            GeneratorError::DownloadError(error) => write!(f, "Download failed: {}", error),
+            GeneratorError::JavaError(error) => write!(f, "Running the server failed: {}", error),
+            GeneratorError::ParseError(error) => write!(f, "Invalid reports: {}", error),
+            GeneratorError::KeptWorkingDir { dir, error } => write!(
+                f,
+                "{} (the working directory was kept at {})",
+                error,
+                dir.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GeneratorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GeneratorError::IoError(error)
+            | GeneratorError::JavaError(error)
+            | GeneratorError::ParseError(error) => Some(error),
+            GeneratorError::DownloadError(error) => Some(error),
+            GeneratorError::KeptWorkingDir { error, .. } => Some(error.as_ref()),
+        }
+    }
+}
@@ -1,32 +1,163 @@
 use crate::data::GeneratedData;
-use server::{download_server, run_server, VersionInfo};
+use crate::error::GeneratorError;
+use server::{download_server_cached, read_jar_version, run_server, Cache, VersionInfo};
 use std::env::temp_dir;
 use std::path::{Path, PathBuf};
 
-pub fn generate_reports_for_version(version: &VersionInfo) -> std::io::Result<GeneratedData> {
-    let temp_dir = temp_dir().join("minecraft_server");
-    std::fs::create_dir(&temp_dir)?;
+pub fn generate_reports_for_version(
+    version: &VersionInfo,
+) -> Result<GeneratedData, GeneratorError> {
+    ReportGenerator::new().generate(version)
+}
+
+/// Like [`generate_reports_for_version`], but takes the server jar from 'cache' if possible
+pub fn generate_reports_for_version_cached(
+    version: &VersionInfo,
+    cache: &Cache,
+) -> Result<GeneratedData, GeneratorError> {
+    ReportGenerator::new()
+        .cache(cache.clone())
+        .generate(version)
+}
+
+/// What happens to the working directory of a run after it finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retention {
+    Delete,
+    /// Keeps the directory of failed runs for debugging
+    KeepOnFailure,
+    Keep,
+}
+
+/// Downloads servers and generates their reports
+///
+/// Every run uses its own working directory, so multiple runs can proceed in parallel.
+#[derive(Debug, Clone)]
+pub struct ReportGenerator {
+    cache: Cache,
+    working_root: PathBuf,
+    retention: Retention,
+}
+
+impl ReportGenerator {
+    pub fn new() -> Self {
+        ReportGenerator {
+            cache: Cache::default(),
+            working_root: temp_dir(),
+            retention: Retention::Delete,
+        }
+    }
+
+    /// Sets the cache the server jars are taken from
+    pub fn cache(mut self, cache: Cache) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Sets the directory in which the working directories are created
+    pub fn working_root(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.working_root = dir.into();
+        self
+    }
+
+    pub fn retention(mut self, retention: Retention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Generates the reports of 'version' in a new working directory
+    ///
+    /// The directory is named `minecraft_server-<version>-<random>` and is removed
+    /// afterwards, unless the [`Retention`] says otherwise. The path of a kept directory is
+    /// returned in [`GeneratedData::working_dir`] or [`GeneratorError::KeptWorkingDir`].
+    pub fn generate(&self, version: &VersionInfo) -> Result<GeneratedData, GeneratorError> {
+        std::fs::create_dir_all(&self.working_root).map_err(GeneratorError::IoError)?;
+        let working_dir = tempfile::Builder::new()
+            .prefix(&format!("minecraft_server-{}-", version.name))
+            .tempdir_in(&self.working_root)
+            .map_err(GeneratorError::IoError)?;
+
+        let result = self.generate_in(version, working_dir.path());
+
+        let keep = match self.retention {
+            Retention::Delete => false,
+            Retention::KeepOnFailure => result.is_err(),
+            Retention::Keep => true,
+        };
+        if keep {
+            let dir = working_dir.keep();
+            return match result {
+                Ok(data) => Ok(GeneratedData {
+                    working_dir: Some(dir),
+                    ..data
+                }),
+                Err(error) => Err(GeneratorError::KeptWorkingDir {
+                    dir,
+                    error: Box::new(error),
+                }),
+            };
+        }
+        if let Err(error) = working_dir.close() {
+            // A failed run reports its own error instead
+            if result.is_ok() {
+                return Err(GeneratorError::IoError(error));
+            }
+        }
+
+        result
+    }
 
-    let server_jar_path = temp_dir.join("server.jar");
-    download_server(version, &server_jar_path)?;
+    fn generate_in(
+        &self,
+        version: &VersionInfo,
+        working_dir: &Path,
+    ) -> Result<GeneratedData, GeneratorError> {
+        let server_jar_path = working_dir.join("server.jar");
+        download_server_cached(version, &server_jar_path, &self.cache)?;
 
-    let reports_dir = generate_reports(server_jar_path)?;
+        let reports_dir = generate_reports(&server_jar_path)?;
 
-    let data = GeneratedData::from_reports_dir(reports_dir)?;
+        let mut data =
+            GeneratedData::from_reports_dir(reports_dir).map_err(GeneratorError::ParseError)?;
+        // Servers older than 1.14 have no version.json
+        data.version = read_jar_version(&server_jar_path).ok();
 
-    std::fs::remove_dir_all(temp_dir)?;
+        Ok(data)
+    }
+}
 
-    Ok(data)
+impl Default for ReportGenerator {
+    fn default() -> Self {
+        ReportGenerator::new()
+    }
 }
 
 /// Generates reports using the given server and returns the path to the reports directory
-pub fn generate_reports(server_jar: impl AsRef<Path>) -> std::io::Result<PathBuf> {
-    let mut process = run_server(
+pub fn generate_reports(server_jar: impl AsRef<Path>) -> Result<PathBuf, GeneratorError> {
+    let process = run_server(
         &server_jar,
         &["--reports"],
         &["-DbundlerMainClass=net.minecraft.data.Main"],
-    )?;
-    process.wait()?;
+    )
+    .map_err(GeneratorError::JavaError)?;
+    // Reading the output keeps the server from blocking on a full pipe
+    let output = process
+        .wait_with_output()
+        .map_err(GeneratorError::JavaError)?;
+    if !output.status.success() {
+        // The server logs most errors to stdout
+        let log = if output.stderr.is_empty() {
+            &output.stdout
+        } else {
+            &output.stderr
+        };
+        return Err(GeneratorError::JavaError(std::io::Error::other(format!(
+            "The server did not exit successfully: {}\n{}",
+            output.status,
+            output_tail(log)
+        ))));
+    }
+
     let report_dir = server_jar
         .as_ref()
         .parent()
@@ -34,3 +165,83 @@ pub fn generate_reports(server_jar: impl AsRef<Path>) -> std::io::Result<PathBuf
         .join("generated/reports");
     Ok(report_dir)
 }
+
+/// The last lines of the output of a process
+fn output_tail(output: &[u8]) -> String {
+    const TAIL_LINES: usize = 20;
+
+    let output = String::from_utf8_lossy(output);
+    let lines: Vec<&str> = output.trim_end().lines().collect();
+    lines[lines.len().saturating_sub(TAIL_LINES)..].join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use server::{Cache, VersionInfo, VersionType};
+
+    use super::{output_tail, ReportGenerator, Retention};
+    use crate::error::GeneratorError;
+
+    fn unavailable_version() -> VersionInfo {
+        VersionInfo {
+            name: "1.20.4".to_string(),
+            typ: VersionType::Release,
+            url: "http://localhost/1.20.4.json".to_string(),
+            sha1: None,
+            compliance_level: None,
+            time: 0,
+            release_time: 0,
+        }
+    }
+
+    #[test]
+    fn test_output_tail() {
+        let output: String = (1..=30).map(|line| format!("line {}\n", line)).collect();
+
+        let tail = output_tail(output.as_bytes());
+        assert!(tail.starts_with("line 11\n"));
+        assert!(tail.ends_with("line 30"));
+        assert_eq!(output_tail(b""), "");
+    }
+
+    #[test]
+    fn test_working_dir_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("work");
+        // Nothing is cached, so the download fails
+        let generator = ReportGenerator::new()
+            .cache(Cache::new(dir.path().join("cache")).offline(true))
+            .working_root(&root);
+
+        let result = generator.generate(&unavailable_version());
+        assert!(matches!(result, Err(GeneratorError::DownloadError(_))));
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 0);
+
+        let result = generator
+            .retention(Retention::KeepOnFailure)
+            .generate(&unavailable_version());
+        let kept: Vec<_> = fs::read_dir(&root).unwrap().collect();
+        assert_eq!(kept.len(), 1);
+        let kept = kept[0].as_ref().unwrap().path();
+        assert!(kept
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("minecraft_server-1.20.4-"));
+        let source = result
+            .as_ref()
+            .err()
+            .and_then(std::error::Error::source)
+            .map(ToString::to_string);
+        assert!(source.is_some_and(|source| source.starts_with("Download failed")));
+        match result {
+            Err(GeneratorError::KeptWorkingDir { dir, error }) => {
+                assert_eq!(dir, kept);
+                assert!(matches!(*error, GeneratorError::DownloadError(_)));
+            }
+            _ => panic!("The kept working directory is not reported"),
+        }
+    }
+}
@@ -1,4 +1,9 @@
 pub mod data;
+mod error;
 mod generator;
 
-pub use generator::{generate_reports, generate_reports_for_version};
+pub use error::GeneratorError;
+pub use generator::{
+    generate_reports, generate_reports_for_version, generate_reports_for_version_cached,
+    ReportGenerator, Retention,
+};
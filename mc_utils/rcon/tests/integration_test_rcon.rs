@@ -2,9 +2,6 @@ use rcon::McRcon;
 use server::{download_server, ServerInstance, VersionManifest};
 use tempfile::{tempdir, TempDir};
 
-pub const RCON_PORT: u16 = 25575;
-pub const RCON_PASSWORD: &str = "1234";
-
 fn setup() -> (TempDir, ServerInstance, McRcon) {
     let dir = tempdir().expect("Could not create a temporary directory");
 
@@ -13,17 +10,15 @@ fn setup() -> (TempDir, ServerInstance, McRcon) {
         .find_version(version_manifest.latest_snapshot())
         .expect("Could not find latest version");
 
-    download_server(&latest_version, dir.path().join("server.jar")).unwrap();
+    download_server(latest_version, dir.path().join("server.jar")).unwrap();
 
     let server = ServerInstance::builder(dir.path())
-        .property("enable-rcon", "true")
-        .property("rcon.password", RCON_PASSWORD)
-        .property("rcon.port", RCON_PORT.to_string())
+        .auto_ports()
+        .enable_rcon()
         .build()
         .expect("Could not start server");
 
-    let rcon = McRcon::new(("localhost", RCON_PORT), RCON_PASSWORD.to_string())
-        .expect("Could not connect rcon");
+    let rcon = server.rcon().expect("Could not connect rcon");
 
     (dir, server, rcon)
 }
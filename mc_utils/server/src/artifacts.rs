@@ -0,0 +1,249 @@
+//! Downloads the jars, mappings and libraries listed in a version json
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    details::{Download, RuleContext, VersionDetails},
+    download::{sha1_file, Checksum, DownloadError, Downloader},
+};
+
+/// A file from the `downloads` section of a version json
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtifactKind {
+    Client,
+    Server,
+    /// The ProGuard mappings of the client jar
+    ClientMappings,
+    /// The ProGuard mappings of the server jar
+    ServerMappings,
+}
+
+impl ArtifactKind {
+    /// The name of the downloaded file, e.g. `1.20.4-client.jar`
+    pub fn file_name(self, version: &str) -> String {
+        match self {
+            ArtifactKind::Client => format!("{}-client.jar", version),
+            ArtifactKind::Server => format!("{}-server.jar", version),
+            ArtifactKind::ClientMappings => format!("{}-client.txt", version),
+            ArtifactKind::ServerMappings => format!("{}-server.txt", version),
+        }
+    }
+}
+
+impl VersionDetails {
+    pub fn artifact(&self, kind: ArtifactKind) -> Option<&Download> {
+        match kind {
+            ArtifactKind::Client => self.downloads.client.as_ref(),
+            ArtifactKind::Server => self.downloads.server.as_ref(),
+            ArtifactKind::ClientMappings => self.downloads.client_mappings.as_ref(),
+            ArtifactKind::ServerMappings => self.downloads.server_mappings.as_ref(),
+        }
+    }
+}
+
+/// Downloads the artifact 'kind' of the version into 'dir' and returns its path
+///
+/// A file which already exists and matches the published hash is not downloaded again.
+pub fn download_artifact(
+    downloader: &Downloader,
+    details: &VersionDetails,
+    kind: ArtifactKind,
+    dir: impl AsRef<Path>,
+) -> Result<PathBuf, DownloadError> {
+    let download = details.artifact(kind).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Version {} has no {:?} download", details.id, kind),
+        )
+    })?;
+
+    fs::create_dir_all(dir.as_ref())?;
+    let path = dir.as_ref().join(kind.file_name(&details.id));
+    download_if_changed(downloader, &download.url, &path, &download.checksum())?;
+    Ok(path)
+}
+
+/// Downloads all libraries which the rules allow on the platform of 'context'
+///
+/// The libraries are stored in the maven layout below 'dir', like the `libraries` directory
+/// of the launcher. Returns the paths of the library jars and native libraries.
+pub fn download_libraries(
+    downloader: &Downloader,
+    details: &VersionDetails,
+    context: &RuleContext,
+    dir: impl AsRef<Path>,
+) -> Result<Vec<PathBuf>, DownloadError> {
+    let mut paths = Vec::new();
+    for library in &details.libraries {
+        for artifact in library.artifacts(context) {
+            if !is_relative_path(&artifact.path) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid library path {}", artifact.path),
+                )
+                .into());
+            }
+
+            let path = dir.as_ref().join(&artifact.path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            let checksum = Checksum {
+                sha1: artifact.sha1.clone(),
+                size: Some(artifact.size),
+            };
+            download_if_changed(downloader, &artifact.url, &path, &checksum)?;
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+/// Whether 'path' stays within the directory it is joined to, i.e. has no root or `..`
+pub(crate) fn is_relative_path(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Downloads 'url' unless 'path' already has the expected hash
+pub(crate) fn download_if_changed(
+    downloader: &Downloader,
+    url: &str,
+    path: &Path,
+    checksum: &Checksum,
+) -> Result<(), DownloadError> {
+    let is_current = path.exists()
+        && sha1_file(path).is_ok_and(|sha1| sha1.eq_ignore_ascii_case(&checksum.sha1));
+    if !is_current {
+        downloader.download(url, path, Some(checksum))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, sync::Arc};
+
+    use super::{download_artifact, download_libraries, ArtifactKind};
+    use crate::{
+        details::{RuleContext, VersionDetails},
+        download::Downloader,
+        http::{HttpClient, StubBackend},
+    };
+
+    const VERSION_JSON: &str = r#"{
+        "id": "1.20.4",
+        "type": "release",
+        "releaseTime": "2023-12-07T12:56:20+00:00",
+        "downloads": {
+            "client": {"sha1": "d2a04d71301a8915217dd5faf81d12cffd6cd958", "size": 6, "url": "http://stub/client.jar"},
+            "client_mappings": {"sha1": "f5b465b16b24ffb0fcef00b86b8635439206dbb5", "size": 8, "url": "http://stub/client.txt"}
+        },
+        "libraries": [
+            {
+                "name": "org.lwjgl:lwjgl:3.3.2",
+                "downloads": {"artifact": {"path": "org/lwjgl/lwjgl/3.3.2/lwjgl-3.3.2.jar", "sha1": "2fc0f185dc60fc6f58085f25e815be749cbcfd9c", "size": 5, "url": "http://stub/lwjgl.jar"}}
+            },
+            {
+                "name": "org.lwjgl:lwjgl:3.3.2:natives-macos",
+                "downloads": {"artifact": {"path": "org/lwjgl/lwjgl/3.3.2/lwjgl-3.3.2-natives-macos.jar", "sha1": "fb3103717e10ef27c5be5504af5004c858897e9e", "size": 7, "url": "http://stub/macos.jar"}},
+                "rules": [{"action": "allow", "os": {"name": "osx"}}]
+            },
+            {
+                "name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4",
+                "downloads": {"classifiers": {"natives-linux": {"path": "org/lwjgl/lwjgl-platform/2.9.4/lwjgl-platform-2.9.4-natives-linux.jar", "sha1": "fb3103717e10ef27c5be5504af5004c858897e9e", "size": 7, "url": "http://stub/natives.jar"}}},
+                "natives": {"linux": "natives-linux"},
+                "rules": [{"action": "allow"}, {"action": "disallow", "os": {"name": "osx"}}]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_download_artifacts() {
+        let backend = Arc::new(
+            StubBackend::new()
+                .route("http://stub/client.jar", "client")
+                .route("http://stub/client.txt", "mappings")
+                .route("http://stub/lwjgl.jar", "lwjgl")
+                .route("http://stub/natives.jar", "natives"),
+        );
+        let downloader = Downloader::new(HttpClient::from_arc(backend.clone()));
+        let details = VersionDetails::from_reader(VERSION_JSON.as_bytes()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+
+        let mappings = download_artifact(
+            &downloader,
+            &details,
+            ArtifactKind::ClientMappings,
+            dir.path(),
+        )
+        .unwrap();
+        assert_eq!(mappings, dir.path().join("1.20.4-client.txt"));
+        assert_eq!(fs::read(mappings).unwrap(), b"mappings");
+        assert!(download_artifact(
+            &downloader,
+            &details,
+            ArtifactKind::ServerMappings,
+            dir.path()
+        )
+        .is_err());
+
+        let libraries_dir = dir.path().join("libraries");
+        let libraries =
+            download_libraries(&downloader, &details, &RuleContext::linux(), &libraries_dir)
+                .unwrap();
+        assert_eq!(
+            libraries,
+            vec![
+                libraries_dir.join("org/lwjgl/lwjgl/3.3.2/lwjgl-3.3.2.jar"),
+                libraries_dir
+                    .join("org/lwjgl/lwjgl-platform/2.9.4/lwjgl-platform-2.9.4-natives-linux.jar"),
+            ]
+        );
+
+        // Files with the correct hash are not downloaded again
+        let requests = backend.requests().len();
+        download_artifact(
+            &downloader,
+            &details,
+            ArtifactKind::ClientMappings,
+            dir.path(),
+        )
+        .unwrap();
+        assert_eq!(backend.requests().len(), requests);
+    }
+
+    #[test]
+    fn test_invalid_library_path() {
+        let details = VersionDetails::from_reader(
+            r#"{
+                "id": "1.20.4",
+                "type": "release",
+                "releaseTime": "2023-12-07T12:56:20+00:00",
+                "downloads": {},
+                "libraries": [{
+                    "name": "evil:evil:1.0",
+                    "downloads": {"artifact": {"path": "../evil.jar", "sha1": "2fc0f185dc60fc6f58085f25e815be749cbcfd9c", "size": 5, "url": "http://stub/lwjgl.jar"}}
+                }]
+            }"#
+            .as_bytes(),
+        )
+        .unwrap();
+        let backend = StubBackend::new().route("http://stub/lwjgl.jar", "lwjgl");
+        let dir = tempfile::tempdir().unwrap();
+
+        let result = download_libraries(
+            &Downloader::new(HttpClient::with_backend(backend)),
+            &details,
+            &RuleContext::linux(),
+            dir.path().join("libraries"),
+        );
+        assert!(result.is_err());
+        assert!(!dir.path().join("evil.jar").exists());
+    }
+}
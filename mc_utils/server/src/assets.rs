@@ -0,0 +1,400 @@
+//! Downloads the asset index of a version and the hashed asset objects
+//!
+//! The files are stored in the layout of the launcher: the index at
+//! `indexes/<id>.json` and the objects at `objects/<first two hex digits>/<hash>`,
+//! so an existing `.minecraft/assets` directory can be reused.
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    artifacts::{download_if_changed, is_relative_path},
+    details::VersionDetails,
+    download::{Checksum, DownloadError, Downloader},
+};
+
+pub const RESOURCES_URL: &str = "https://resources.download.minecraft.net";
+
+/// The list of assets of a version
+#[derive(Debug, Clone, Deserialize)]
+pub struct AssetIndexFile {
+    /// Maps the asset names, e.g. `minecraft/lang/de_de.json`, to their objects
+    pub objects: HashMap<String, AssetObject>,
+    /// Old versions expect the assets with their names in the resources directory of the game
+    #[serde(default)]
+    pub map_to_resources: bool,
+    /// Old versions expect the assets with their names in `virtual/<index id>`
+    #[serde(default, rename = "virtual")]
+    pub is_virtual: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct AssetObject {
+    pub hash: String,
+    pub size: u64,
+}
+
+impl AssetObject {
+    /// The path of the object relative to the objects directory and the resources url
+    pub fn path(&self) -> String {
+        format!("{}/{}", self.hash.get(..2).unwrap_or_default(), self.hash)
+    }
+
+    /// Whether the hash consists of 40 hex digits, only then it is safe to use in paths
+    pub fn has_valid_hash(&self) -> bool {
+        self.hash.len() == 40 && self.hash.bytes().all(|byte| byte.is_ascii_hexdigit())
+    }
+}
+
+/// Downloads assets using several threads
+#[derive(Clone)]
+pub struct AssetDownloader {
+    downloader: Downloader,
+    resources_url: String,
+    threads: usize,
+    resources_dir: Option<PathBuf>,
+}
+
+impl AssetDownloader {
+    pub fn new(downloader: Downloader) -> Self {
+        AssetDownloader {
+            downloader,
+            resources_url: RESOURCES_URL.to_string(),
+            threads: 8,
+            resources_dir: None,
+        }
+    }
+
+    /// Downloads the objects from 'url' instead of the official resources endpoint
+    pub fn resources_url(mut self, url: impl Into<String>) -> Self {
+        self.resources_url = url.into();
+        self
+    }
+
+    /// The number of parallel downloads
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// The directory into which the assets of indexes with `map_to_resources` are copied,
+    /// usually `<game dir>/resources`
+    ///
+    /// Without it, these assets are copied into `virtual/<index id>` of the assets directory.
+    pub fn resources_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.resources_dir = Some(dir.into());
+        self
+    }
+
+    /// Downloads the asset index and all objects of the version into 'assets_dir'
+    ///
+    /// The assets of old versions are additionally copied to their names,
+    /// see [`AssetIndexFile::is_virtual`] and [`AssetIndexFile::map_to_resources`].
+    pub fn download_assets(
+        &self,
+        details: &VersionDetails,
+        assets_dir: impl AsRef<Path>,
+    ) -> Result<AssetIndexFile, DownloadError> {
+        let assets_dir = assets_dir.as_ref();
+        let index = self.download_index(details, assets_dir)?;
+        self.download_objects(&index, assets_dir)?;
+
+        if index.is_virtual || index.map_to_resources {
+            // download_index fails for versions without an asset index
+            let id = details
+                .asset_index
+                .as_ref()
+                .map_or("", |index| index.id.as_str());
+            let virtual_dir = assets_dir.join("virtual").join(id);
+            let target = match &self.resources_dir {
+                Some(resources_dir) if index.map_to_resources => resources_dir,
+                _ => &virtual_dir,
+            };
+            copy_named_objects(&index, &assets_dir.join("objects"), target)?;
+        }
+        Ok(index)
+    }
+
+    /// Downloads the asset index of the version to `indexes/<id>.json` and parses it
+    pub fn download_index(
+        &self,
+        details: &VersionDetails,
+        assets_dir: impl AsRef<Path>,
+    ) -> Result<AssetIndexFile, DownloadError> {
+        let asset_index = details.asset_index.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Version {} has no asset index", details.id),
+            )
+        })?;
+
+        let indexes_dir = assets_dir.as_ref().join("indexes");
+        fs::create_dir_all(&indexes_dir)?;
+        let path = indexes_dir.join(format!("{}.json", asset_index.id));
+        let checksum = Checksum {
+            sha1: asset_index.sha1.clone(),
+            size: Some(asset_index.size),
+        };
+        download_if_changed(&self.downloader, &asset_index.url, &path, &checksum)?;
+
+        let index = serde_json::from_reader(BufReader::new(fs::File::open(path)?))
+            .map_err(io::Error::from)?;
+        Ok(index)
+    }
+
+    /// Downloads all objects of 'index' to `objects/` and returns their paths
+    ///
+    /// Objects which already exist with the correct size are skipped, since their
+    /// file names are their hashes.
+    /// After the first failure no new downloads are started and the error is returned.
+    pub fn download_objects(
+        &self,
+        index: &AssetIndexFile,
+        assets_dir: impl AsRef<Path>,
+    ) -> Result<Vec<PathBuf>, DownloadError> {
+        let objects_dir = assets_dir.as_ref().join("objects");
+        let mut objects: Vec<&AssetObject> = index.objects.values().collect();
+        objects.sort_unstable_by(|a, b| a.hash.cmp(&b.hash));
+        objects.dedup();
+        if let Some(object) = objects.iter().find(|object| !object.has_valid_hash()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid asset hash {}", object.hash),
+            )
+            .into());
+        }
+
+        let paths: Vec<PathBuf> = objects
+            .iter()
+            .map(|object| objects_dir.join(object.path()))
+            .collect();
+        let queue = Mutex::new(objects.into_iter().zip(&paths));
+        let error: Mutex<Option<DownloadError>> = Mutex::new(None);
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                scope.spawn(|| loop {
+                    if error.lock().unwrap().is_some() {
+                        break;
+                    }
+                    let (object, path) = match queue.lock().unwrap().next() {
+                        Some(next) => next,
+                        None => break,
+                    };
+
+                    if let Err(err) = self.download_object(object, path) {
+                        error.lock().unwrap().get_or_insert(err);
+                    }
+                });
+            }
+        });
+
+        match error.into_inner().unwrap() {
+            Some(error) => Err(error),
+            None => Ok(paths),
+        }
+    }
+
+    fn download_object(&self, object: &AssetObject, path: &Path) -> Result<(), DownloadError> {
+        if has_size(path, object.size) {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let url = format!("{}/{}", self.resources_url, object.path());
+        let checksum = Checksum {
+            sha1: object.hash.clone(),
+            size: Some(object.size),
+        };
+        self.downloader.download(&url, path, Some(&checksum))?;
+        Ok(())
+    }
+}
+
+/// Copies the objects of 'index' from 'objects_dir' to their names below 'target'
+fn copy_named_objects(index: &AssetIndexFile, objects_dir: &Path, target: &Path) -> io::Result<()> {
+    for (name, object) in &index.objects {
+        if !is_relative_path(name) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid asset name {}", name),
+            ));
+        }
+
+        let path = target.join(name);
+        if has_size(&path, object.size) {
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(objects_dir.join(object.path()), path)?;
+    }
+    Ok(())
+}
+
+fn has_size(path: &Path, size: u64) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.len() == size)
+}
+
+impl Default for AssetDownloader {
+    fn default() -> Self {
+        AssetDownloader::new(Downloader::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, sync::Arc};
+
+    use sha1::{Digest, Sha1};
+
+    use super::{AssetDownloader, AssetIndexFile};
+    use crate::{
+        details::VersionDetails,
+        download::{hex, Downloader},
+        http::{HttpClient, StubBackend},
+    };
+
+    #[test]
+    fn test_download_assets() {
+        let index = r#"{"objects": {
+            "pack.mcmeta": {"hash": "facf66626f0109c3ddf853d456e7aedc02a00a9c", "size": 11},
+            "minecraft/sounds/a.ogg": {"hash": "5e2e97c3c7ba3035c6e6fc3a17e1bc22029c0c3e", "size": 5},
+            "minecraft/sounds/b.ogg": {"hash": "5e2e97c3c7ba3035c6e6fc3a17e1bc22029c0c3e", "size": 5}
+        }}"#;
+        let details = format!(
+            r#"{{
+                "id": "1.20.4",
+                "type": "release",
+                "releaseTime": "2023-12-07T12:56:20+00:00",
+                "downloads": {{}},
+                "assetIndex": {{"id": "12", "sha1": "{}", "size": {}, "url": "http://stub/12.json"}}
+            }}"#,
+            hex(&Sha1::digest(index.as_bytes())),
+            index.len()
+        );
+        let details = VersionDetails::from_reader(details.as_bytes()).unwrap();
+
+        let backend = StubBackend::new()
+            .route("http://stub/12.json", index)
+            .route(
+                "http://resources/fa/facf66626f0109c3ddf853d456e7aedc02a00a9c",
+                r#"{"pack":{}}"#,
+            )
+            .route(
+                "http://resources/5e/5e2e97c3c7ba3035c6e6fc3a17e1bc22029c0c3e",
+                "sound",
+            );
+        let assets = AssetDownloader::new(Downloader::new(HttpClient::with_backend(backend)))
+            .resources_url("http://resources")
+            .threads(2);
+        let dir = tempfile::tempdir().unwrap();
+
+        let index = assets.download_assets(&details, dir.path()).unwrap();
+
+        assert_eq!(index.objects.len(), 3);
+        assert!(dir.path().join("indexes/12.json").exists());
+        assert_eq!(
+            fs::read(
+                dir.path()
+                    .join("objects/5e/5e2e97c3c7ba3035c6e6fc3a17e1bc22029c0c3e")
+            )
+            .unwrap(),
+            b"sound"
+        );
+    }
+
+    #[test]
+    fn test_invalid_asset_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let assets = AssetDownloader::new(Downloader::new(HttpClient::with_backend(
+            StubBackend::new(),
+        )));
+
+        for hash in [
+            "../../../../evil",
+            "äbcdef",
+            "5e2e97c3c7ba3035c6e6fc3a17e1bc22029c0c3",
+        ] {
+            let index: AssetIndexFile = serde_json::from_str(&format!(
+                r#"{{"objects": {{"evil": {{"hash": "{}", "size": 5}}}}}}"#,
+                hash
+            ))
+            .unwrap();
+            assert!(assets.download_objects(&index, dir.path()).is_err());
+        }
+        assert!(!dir.path().join("objects").exists());
+    }
+
+    /// A version json of an old version, whose asset index is served at 'url'
+    fn legacy_details(url: &str, index: &str) -> VersionDetails {
+        let details = format!(
+            r#"{{
+                "id": "1.5.2",
+                "type": "release",
+                "releaseTime": "2013-04-25T15:45:00+00:00",
+                "downloads": {{}},
+                "assetIndex": {{"id": "legacy", "sha1": "{}", "size": {}, "url": "{}"}}
+            }}"#,
+            hex(&Sha1::digest(index.as_bytes())),
+            index.len(),
+            url
+        );
+        VersionDetails::from_reader(details.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_named_assets() {
+        let virtual_index = r#"{"virtual": true, "objects": {"lang/en_US.lang": {"hash": "5e2e97c3c7ba3035c6e6fc3a17e1bc22029c0c3e", "size": 5}}}"#;
+        let resources_index = r#"{"map_to_resources": true, "objects": {"sound/a.ogg": {"hash": "5e2e97c3c7ba3035c6e6fc3a17e1bc22029c0c3e", "size": 5}}}"#;
+        let backend = Arc::new(
+            StubBackend::new()
+                .route("http://stub/virtual.json", virtual_index)
+                .route("http://stub/resources.json", resources_index),
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let assets_dir = dir.path().join("assets");
+        // Existing objects with the correct size are not downloaded again
+        let object = assets_dir.join("objects/5e/5e2e97c3c7ba3035c6e6fc3a17e1bc22029c0c3e");
+        fs::create_dir_all(object.parent().unwrap()).unwrap();
+        fs::write(&object, "sound").unwrap();
+
+        let resources_dir = dir.path().join("resources");
+        let assets = AssetDownloader::new(Downloader::new(HttpClient::from_arc(backend.clone())))
+            .resources_url("http://resources")
+            .resources_dir(&resources_dir);
+
+        let details = legacy_details("http://stub/virtual.json", virtual_index);
+        assert!(
+            assets
+                .download_assets(&details, &assets_dir)
+                .unwrap()
+                .is_virtual
+        );
+        assert_eq!(
+            fs::read(assets_dir.join("virtual/legacy/lang/en_US.lang")).unwrap(),
+            b"sound"
+        );
+
+        let details = legacy_details("http://stub/resources.json", resources_index);
+        assets.download_assets(&details, &assets_dir).unwrap();
+        assert_eq!(
+            fs::read(resources_dir.join("sound/a.ogg")).unwrap(),
+            b"sound"
+        );
+
+        assert!(backend
+            .requests()
+            .iter()
+            .all(|request| !request.url.starts_with("http://resources")));
+    }
+}
@@ -0,0 +1,468 @@
+//! Backups of the world of a server
+//!
+//! Backups are stored as `<world>_<timestamp>.tar.gz` archives, which contain the contents of the
+//! world directory. Backups created within the same second get a `_<n>` suffix after the timestamp.
+use std::{
+    cmp::Reverse,
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use chrono::{DateTime, Datelike, NaiveDateTime, Timelike, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+use crate::instance::{InstanceError, Result, ServerInstance};
+
+const ARCHIVE_EXTENSION: &str = ".tar.gz";
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d_%H-%M-%S";
+/// The length of a timestamp formatted with `TIMESTAMP_FORMAT`
+const TIMESTAMP_LENGTH: usize = 19;
+/// The lock file of a running server, which must not be copied
+const SESSION_LOCK: &str = "session.lock";
+/// How long to wait for the world to be saved if rcon is not available
+const SAVE_DELAY: Duration = Duration::from_secs(5);
+
+/// Decides which backups are kept
+///
+/// A backup is kept if any of the rules selects it.
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    /// Keeps the newest `keep_last` backups
+    pub keep_last: usize,
+    /// Keeps the newest backup of each of the last `keep_daily` days that have backups
+    pub keep_daily: usize,
+    /// Keeps the newest backup of each of the last `keep_weekly` weeks that have backups
+    pub keep_weekly: usize,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            keep_last: 10,
+            keep_daily: 7,
+            keep_weekly: 4,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// Returns the indices of the backups which should be deleted
+    ///
+    /// 'times' must be sorted from newest to oldest.
+    pub fn expired(&self, times: &[DateTime<Utc>]) -> Vec<usize> {
+        let mut keep = HashSet::new();
+        keep.extend(0..self.keep_last.min(times.len()));
+
+        let mut days = HashSet::new();
+        let mut weeks = HashSet::new();
+        for (index, time) in times.iter().enumerate() {
+            if days.len() < self.keep_daily && days.insert(time.date_naive()) {
+                keep.insert(index);
+            }
+            let week = time.iso_week();
+            if weeks.len() < self.keep_weekly && weeks.insert((week.year(), week.week())) {
+                keep.insert(index);
+            }
+        }
+
+        (0..times.len())
+            .filter(|index| !keep.contains(index))
+            .collect()
+    }
+}
+
+/// A single backup archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub created: DateTime<Utc>,
+}
+
+/// Manages the backups in a directory
+#[derive(Debug, Clone)]
+pub struct Backups {
+    pub dir: PathBuf,
+    pub retention: RetentionPolicy,
+}
+
+impl Backups {
+    /// Stores backups in 'dir' using the default retention policy
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Backups {
+            dir: dir.into(),
+            retention: RetentionPolicy::default(),
+        }
+    }
+
+    /// Sets the retention policy, which is applied after every backup
+    pub fn retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Lists the backups of the world 'world_name', newest first
+    pub fn list(&self, world_name: &str) -> io::Result<Vec<Backup>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let prefix = format!("{}_", world_name);
+        let mut backups = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let parsed = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(&prefix))
+                .and_then(|name| name.strip_suffix(ARCHIVE_EXTENSION))
+                .and_then(parse_timestamp);
+            if let Some((timestamp, counter)) = parsed {
+                let backup = Backup {
+                    path,
+                    created: timestamp.and_utc(),
+                };
+                backups.push((counter, backup));
+            }
+        }
+
+        backups.sort_unstable_by_key(|(counter, backup)| Reverse((backup.created, *counter)));
+        Ok(backups.into_iter().map(|(_, backup)| backup).collect())
+    }
+
+    /// Creates a backup of the world of a running server and applies the retention policy
+    ///
+    /// Saving is disabled while the world is copied, so that the archive is consistent.
+    ///
+    /// If saving cannot be turned on again after the backup was created,
+    /// [`InstanceError::SavingNotResumed`] is returned.
+    pub fn create(&self, instance: &mut ServerInstance) -> Result<Backup> {
+        save_command(instance, "save-off")?;
+        let result = save_command(instance, "save-all flush").and_then(|_| {
+            let world_dir = instance.dir.join(&instance.world_name);
+            Ok(self.create_from_dir(world_dir, &instance.world_name)?)
+        });
+        let save_on = save_command(instance, "save-on");
+
+        let backup = result?;
+        let retention = self.apply_retention(&instance.world_name);
+        if let Err(error) = save_on {
+            return Err(InstanceError::SavingNotResumed {
+                backup: backup.path,
+                error: Box::new(error),
+            });
+        }
+        retention?;
+        Ok(backup)
+    }
+
+    /// Creates a backup if the latest backup is older than 'interval'
+    ///
+    /// Calling this periodically backs up the world in regular intervals.
+    pub fn create_if_due(
+        &self,
+        instance: &mut ServerInstance,
+        interval: Duration,
+    ) -> Result<Option<Backup>> {
+        let latest = self.list(&instance.world_name)?.into_iter().next();
+        let is_due = latest.is_none_or(|backup| {
+            let age = Utc::now().signed_duration_since(backup.created);
+            age.to_std().is_ok_and(|age| age >= interval)
+        });
+
+        if is_due {
+            self.create(instance).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Archives the world directory 'world_dir' without any interaction with a server
+    pub fn create_from_dir(
+        &self,
+        world_dir: impl AsRef<Path>,
+        world_name: &str,
+    ) -> io::Result<Backup> {
+        fs::create_dir_all(&self.dir)?;
+
+        // The file name only stores whole seconds
+        let created = Utc::now()
+            .with_nanosecond(0)
+            .expect("Zero nanoseconds are always valid");
+        let name = format!("{}_{}", world_name, created.format(TIMESTAMP_FORMAT));
+        let (path, file) = create_new_archive(&self.dir, &name)?;
+        if let Err(error) = write_archive(world_dir, file) {
+            fs::remove_file(&path).ok();
+            return Err(error);
+        }
+
+        Ok(Backup { path, created })
+    }
+
+    /// Deletes all backups of 'world_name' which are not kept by the retention policy
+    ///
+    /// Returns the deleted backups.
+    pub fn apply_retention(&self, world_name: &str) -> io::Result<Vec<Backup>> {
+        let backups = self.list(world_name)?;
+        let times: Vec<_> = backups.iter().map(|backup| backup.created).collect();
+
+        let mut deleted = Vec::new();
+        for index in self.retention.expired(&times) {
+            fs::remove_file(&backups[index].path)?;
+            deleted.push(backups[index].clone());
+        }
+        Ok(deleted)
+    }
+}
+
+/// Replaces the world directory 'world_dir' with the contents of the backup 'archive'
+///
+/// The archive is extracted next to the world first, so the world is left untouched if
+/// the archive is broken. The server must not be running while its world is restored.
+pub fn restore_backup(archive: impl AsRef<Path>, world_dir: impl AsRef<Path>) -> io::Result<()> {
+    replace_dir(world_dir.as_ref(), |new_dir| {
+        extract_archive(archive, new_dir)
+    })
+}
+
+/// Replaces 'dir' with the directory created by 'create'
+///
+/// 'create' fills a sibling directory, so 'dir' is only touched once that succeeded.
+pub(crate) fn replace_dir(
+    dir: &Path,
+    create: impl FnOnce(&Path) -> io::Result<()>,
+) -> io::Result<()> {
+    let new_dir = sibling_dir(dir, "new")?;
+    let old_dir = sibling_dir(dir, "old")?;
+
+    if let Err(error) = create(&new_dir) {
+        fs::remove_dir_all(&new_dir).ok();
+        return Err(error);
+    }
+
+    if !dir.exists() {
+        return fs::rename(&new_dir, dir);
+    }
+    fs::rename(dir, &old_dir)?;
+    if let Err(error) = fs::rename(&new_dir, dir) {
+        fs::rename(&old_dir, dir)?;
+        return Err(error);
+    }
+    fs::remove_dir_all(old_dir)
+}
+
+/// Returns the path `<dir>.<suffix>` and removes leftovers of an interrupted replacement there
+fn sibling_dir(dir: &Path, suffix: &str) -> io::Result<PathBuf> {
+    let mut name = dir
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid world directory"))?
+        .to_os_string();
+    name.push(".");
+    name.push(suffix);
+
+    let sibling = dir.with_file_name(name);
+    if sibling.exists() {
+        fs::remove_dir_all(&sibling)?;
+    }
+    Ok(sibling)
+}
+
+/// Writes the contents of 'dir' as compressed tar archive into 'file'
+pub(crate) fn write_archive(dir: impl AsRef<Path>, file: File) -> io::Result<()> {
+    let encoder = GzEncoder::new(file, Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    append_dir(&mut builder, dir.as_ref(), Path::new(""))?;
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Creates the archive file for 'name' in 'dir' without replacing an existing backup
+///
+/// If the name is taken, a counter is appended.
+fn create_new_archive(dir: &Path, name: &str) -> io::Result<(PathBuf, File)> {
+    let mut counter = 0;
+    loop {
+        let file_name = if counter == 0 {
+            format!("{}{}", name, ARCHIVE_EXTENSION)
+        } else {
+            format!("{}_{}{}", name, counter, ARCHIVE_EXTENSION)
+        };
+        let path = dir.join(file_name);
+
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => counter += 1,
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// Parses `<timestamp>` or `<timestamp>_<counter>`
+fn parse_timestamp(name: &str) -> Option<(NaiveDateTime, u32)> {
+    let timestamp = name.get(..TIMESTAMP_LENGTH)?;
+    let counter = match &name[TIMESTAMP_LENGTH..] {
+        "" => 0,
+        rest => rest.strip_prefix('_')?.parse().ok()?,
+    };
+    let timestamp = NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()?;
+    Some((timestamp, counter))
+}
+
+/// Extracts the compressed tar archive at 'archive' into 'dir'
+pub(crate) fn extract_archive(archive: impl AsRef<Path>, dir: impl AsRef<Path>) -> io::Result<()> {
+    let decoder = GzDecoder::new(File::open(archive)?);
+    fs::create_dir_all(&dir)?;
+    tar::Archive::new(decoder).unpack(dir)
+}
+
+fn append_dir<W: io::Write>(
+    builder: &mut tar::Builder<W>,
+    dir: &Path,
+    archive_path: &Path,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name == SESSION_LOCK {
+            continue;
+        }
+
+        let path = archive_path.join(&name);
+        if entry.file_type()?.is_dir() {
+            builder.append_dir(&path, entry.path())?;
+            append_dir(builder, &entry.path(), &path)?;
+        } else {
+            builder.append_path_with_name(entry.path(), &path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs a save related command and waits until it is processed
+///
+/// Rcon responds only after the command finished, so it is used if available.
+fn save_command(instance: &mut ServerInstance, command: &str) -> Result<()> {
+    if instance.rcon_enabled() {
+        instance.rcon()?.command(command)?;
+    } else {
+        instance.command(command)?;
+        thread::sleep(SAVE_DELAY);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use super::{restore_backup, Backups, RetentionPolicy};
+
+    fn time(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 5, day, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_retention_policy() {
+        let policy = RetentionPolicy {
+            keep_last: 2,
+            keep_daily: 3,
+            keep_weekly: 0,
+        };
+        // Newest first
+        let times = [
+            time(10, 12),
+            time(10, 8),
+            time(10, 4),
+            time(9, 12),
+            time(9, 6),
+            time(8, 12),
+            time(7, 12),
+        ];
+
+        assert_eq!(policy.expired(&times), vec![2, 4, 6]);
+    }
+
+    #[test]
+    fn test_weekly_retention() {
+        let policy = RetentionPolicy {
+            keep_last: 0,
+            keep_daily: 0,
+            keep_weekly: 2,
+        };
+        // 2023-05-15 is a monday
+        let times = [
+            time(16, 0),
+            time(15, 0),
+            time(14, 0),
+            time(8, 0),
+            time(1, 0),
+        ];
+
+        assert_eq!(policy.expired(&times), vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn test_backup_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let world = dir.path().join("world");
+        fs::create_dir_all(world.join("region")).unwrap();
+        fs::write(world.join("level.dat"), b"level").unwrap();
+        fs::write(world.join("region/r.0.0.mca"), b"region").unwrap();
+        fs::write(world.join("session.lock"), b"lock").unwrap();
+
+        let backups = Backups::new(dir.path().join("backups"));
+        let backup = backups.create_from_dir(&world, "world").unwrap();
+        assert_eq!(backups.list("world").unwrap(), vec![backup.clone()]);
+
+        fs::write(world.join("level.dat"), b"changed").unwrap();
+        restore_backup(&backup.path, &world).unwrap();
+
+        assert_eq!(fs::read(world.join("level.dat")).unwrap(), b"level");
+        assert_eq!(fs::read(world.join("region/r.0.0.mca")).unwrap(), b"region");
+        assert!(!world.join("session.lock").exists());
+    }
+
+    #[test]
+    fn test_backups_in_same_second() {
+        let dir = tempfile::tempdir().unwrap();
+        let world = dir.path().join("world");
+        fs::create_dir_all(&world).unwrap();
+        fs::write(world.join("level.dat"), b"first").unwrap();
+
+        let backups = Backups::new(dir.path().join("backups"));
+        let first = backups.create_from_dir(&world, "world").unwrap();
+        fs::write(world.join("level.dat"), b"second").unwrap();
+        let second = backups.create_from_dir(&world, "world").unwrap();
+        let _ = backups.create_from_dir(&world, "world").unwrap();
+
+        assert_ne!(first.path, second.path);
+        let listed = backups.list("world").unwrap();
+        assert_eq!(listed.len(), 3);
+        // Within the same second, the later backup is newer
+        if first.created == second.created {
+            assert!(listed.ends_with(&[second, first.clone()]));
+        }
+
+        restore_backup(&first.path, &world).unwrap();
+        assert_eq!(fs::read(world.join("level.dat")).unwrap(), b"first");
+    }
+
+    #[test]
+    fn test_restore_broken_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let world = dir.path().join("world");
+        fs::create_dir_all(&world).unwrap();
+        fs::write(world.join("level.dat"), b"level").unwrap();
+        let archive = dir.path().join("broken.tar.gz");
+        fs::write(&archive, b"not an archive").unwrap();
+
+        assert!(restore_backup(&archive, &world).is_err());
+        assert_eq!(fs::read(world.join("level.dat")).unwrap(), b"level");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
@@ -0,0 +1,295 @@
+//! A local cache for downloaded manifests, version jsons and jars
+//!
+//! Files with a known sha1 hash, like server jars, are stored content addressed under
+//! `objects/<first two hex digits>/<sha1>`. Documents without a known hash, like the version
+//! manifest, are stored by their url under `documents/` and refreshed after a time to live.
+//!
+//! Files are written to a unique temporary file first and then renamed into place,
+//! so multiple processes can share a cache.
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, SystemTime},
+};
+
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use sha1::{Digest, Sha1};
+
+use crate::{
+    download::{hex, Checksum, DownloadError, Downloader},
+    http::HttpClient,
+};
+
+/// The default time after which the version manifest is downloaded again
+pub const DEFAULT_MANIFEST_TTL: Duration = Duration::from_secs(60 * 60);
+
+const OBJECTS_DIR: &str = "objects";
+const DOCUMENTS_DIR: &str = "documents";
+
+#[derive(Debug, Clone)]
+pub struct Cache {
+    pub dir: PathBuf,
+    /// The time after which the version manifest is considered stale
+    pub manifest_ttl: Duration,
+    /// Never access the network and serve everything from the cache
+    pub offline: bool,
+    pub client: HttpClient,
+}
+
+impl Cache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Cache {
+            dir: dir.into(),
+            manifest_ttl: DEFAULT_MANIFEST_TTL,
+            offline: false,
+            client: HttpClient::default(),
+        }
+    }
+
+    /// The platform specific cache directory, e.g. `~/.cache/mc_utils` on linux
+    pub fn default_dir() -> PathBuf {
+        dirs::cache_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("mc_utils")
+    }
+
+    pub fn manifest_ttl(mut self, ttl: Duration) -> Self {
+        self.manifest_ttl = ttl;
+        self
+    }
+
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Sets the client used for downloads
+    pub fn client(mut self, client: HttpClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// The location of the object with the given sha1 hash, which might not exist yet
+    pub fn object_path(&self, sha1: &str) -> PathBuf {
+        let sha1 = sha1.to_ascii_lowercase();
+        let prefix = sha1.get(..2).unwrap_or_default();
+        self.dir.join(OBJECTS_DIR).join(prefix).join(&sha1)
+    }
+
+    /// Returns the path of the cached object, downloading and verifying it first if necessary
+    pub fn fetch_object(&self, url: &str, checksum: &Checksum) -> Result<PathBuf, DownloadError> {
+        let path = self.object_path(&checksum.sha1);
+        if path.exists() {
+            return Ok(path);
+        }
+        if self.offline {
+            return Err(not_cached(url).into());
+        }
+
+        fs::create_dir_all(path.parent().expect("Objects always have a parent"))?;
+        let temp_path = unique_temp_path(&path);
+        // A partial download under the unique name could never be resumed, so it is removed
+        let downloader = Downloader::new(self.client.clone()).resume(false);
+        if let Err(error) = downloader.download(url, &temp_path, Some(checksum)) {
+            fs::remove_file(&temp_path).ok();
+            return Err(error);
+        }
+        fs::rename(&temp_path, &path)?;
+        Ok(path)
+    }
+
+    /// Copies the cached object to 'destination', downloading it first if necessary
+    pub fn copy_object(
+        &self,
+        url: &str,
+        checksum: &Checksum,
+        destination: impl AsRef<Path>,
+    ) -> Result<(), DownloadError> {
+        let path = self.fetch_object(url, checksum)?;
+        fs::copy(path, destination)?;
+        Ok(())
+    }
+
+    /// Returns the content of the document at 'url'
+    ///
+    /// The cached document is used if it is younger than 'ttl', or if the cache is offline.
+    /// A `ttl` of `None` means that the document never changes.
+    /// If the download fails, a stale cached document is used as fallback.
+    pub fn fetch_document(&self, url: &str, ttl: Option<Duration>) -> io::Result<Vec<u8>> {
+        let path = self.document_path(url);
+        let age = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .map(|modified| {
+                SystemTime::now()
+                    .duration_since(modified)
+                    .unwrap_or_default()
+            });
+
+        let is_fresh = match (age, ttl) {
+            (Some(age), Some(ttl)) => age < ttl,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        if is_fresh || (self.offline && age.is_some()) {
+            return fs::read(path);
+        }
+        if self.offline {
+            return Err(not_cached(url));
+        }
+
+        match self.client.get_bytes(url) {
+            Ok(content) => {
+                fs::create_dir_all(path.parent().expect("Documents always have a parent"))?;
+                let temp_path = unique_temp_path(&path);
+                fs::write(&temp_path, &content)?;
+                fs::rename(&temp_path, &path)?;
+                Ok(content)
+            }
+            Err(_) if age.is_some() => fs::read(path),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Documents are stored by the sha1 hash of their url
+    fn document_path(&self, url: &str) -> PathBuf {
+        self.dir
+            .join(DOCUMENTS_DIR)
+            .join(hex(&Sha1::digest(url.as_bytes())))
+    }
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Cache::new(Cache::default_dir())
+    }
+}
+
+/// A temporary path next to 'path' which is not used by any other process or thread
+fn unique_temp_path(path: &Path) -> PathBuf {
+    let suffix: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(8)
+        .map(char::from)
+        .collect();
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(".{}-{}.tmp", process::id(), suffix));
+    path.with_file_name(file_name)
+}
+
+fn not_cached(url: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} is not cached and the cache is offline", url),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        io::{self, Read},
+        time::Duration,
+    };
+
+    use super::Cache;
+    use crate::{
+        download::Checksum,
+        http::{HttpBackend, HttpClient, HttpRequest, HttpResponse},
+        test_util::serve,
+    };
+
+    #[test]
+    fn test_fetch_object() {
+        let url = serve(vec![("/file", b"hello world".to_vec())]);
+        let dir = tempfile::tempdir().unwrap();
+        let checksum = Checksum {
+            sha1: "2aae6c35c94fcfb415dbe95f408b9ce91ee846ed".to_string(),
+            size: Some(11),
+        };
+
+        let cache = Cache::new(dir.path());
+        let path = cache
+            .fetch_object(&format!("{}/file", url), &checksum)
+            .unwrap();
+        assert_eq!(path, dir.path().join("objects/2a").join(&checksum.sha1));
+        // No temporary files are left behind
+        assert_eq!(
+            fs::read_dir(dir.path().join("objects/2a")).unwrap().count(),
+            1
+        );
+
+        // The object is served from the cache, even though the url does not exist
+        let offline = Cache::new(dir.path()).offline(true);
+        assert_eq!(
+            offline.fetch_object("http://invalid", &checksum).unwrap(),
+            path
+        );
+    }
+
+    /// Answers every request with a body which breaks off after a few bytes
+    #[derive(Debug)]
+    struct BrokenBackend;
+
+    impl HttpBackend for BrokenBackend {
+        fn send(&self, _request: &HttpRequest) -> io::Result<HttpResponse> {
+            let broken = io::Error::new(io::ErrorKind::ConnectionReset, "reset");
+            Ok(HttpResponse {
+                status: 200,
+                headers: Vec::new(),
+                body: Box::new(Read::chain(&b"hello"[..], BrokenReader(Some(broken)))),
+            })
+        }
+    }
+
+    struct BrokenReader(Option<io::Error>);
+
+    impl Read for BrokenReader {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(self
+                .0
+                .take()
+                .unwrap_or_else(|| io::ErrorKind::ConnectionReset.into()))
+        }
+    }
+
+    #[test]
+    fn test_failed_fetch_object() {
+        let dir = tempfile::tempdir().unwrap();
+        let checksum = Checksum {
+            sha1: "2aae6c35c94fcfb415dbe95f408b9ce91ee846ed".to_string(),
+            size: Some(11),
+        };
+
+        let cache = Cache::new(dir.path()).client(HttpClient::with_backend(BrokenBackend));
+        assert!(cache.fetch_object("http://stub/file", &checksum).is_err());
+        // Neither the temporary file nor a partial download is left behind
+        assert_eq!(
+            fs::read_dir(dir.path().join("objects/2a")).unwrap().count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_fetch_document() {
+        let url = format!(
+            "{}/manifest.json",
+            serve(vec![("/manifest.json", b"{}".to_vec())])
+        );
+        let dir = tempfile::tempdir().unwrap();
+
+        let offline = Cache::new(dir.path()).offline(true);
+        assert!(offline.fetch_document(&url, None).is_err());
+
+        let cache = Cache::new(dir.path());
+        assert_eq!(
+            cache.fetch_document(&url, Some(Duration::ZERO)).unwrap(),
+            b"{}"
+        );
+        assert_eq!(
+            offline.fetch_document(&url, Some(Duration::ZERO)).unwrap(),
+            b"{}"
+        );
+    }
+}
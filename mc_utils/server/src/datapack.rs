@@ -0,0 +1,201 @@
+//! Installing datapacks into the world of a server
+use std::{
+    fs, io,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    instance::{InstanceError, Result, ServerInstance},
+    world::copy_dir_all,
+};
+
+pub const DATAPACKS_DIR: &str = "datapacks";
+
+/// How long to wait for a datapack to be enabled after a reload
+const ENABLE_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long to wait for the response to `datapack list`
+const LIST_TIMEOUT: Duration = Duration::from_secs(5);
+const LIST_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Copies the datapack folder or zip file at 'datapack' into the datapacks folder of 'world_dir'
+pub fn install_datapack_files(
+    datapack: impl AsRef<Path>,
+    world_dir: impl AsRef<Path>,
+) -> io::Result<()> {
+    let datapack = datapack.as_ref();
+    let datapacks_dir = world_dir.as_ref().join(DATAPACKS_DIR);
+    fs::create_dir_all(&datapacks_dir)?;
+
+    let target = datapacks_dir.join(datapack_file_name(datapack)?);
+    if datapack.is_dir() {
+        if target.exists() {
+            fs::remove_dir_all(&target)?;
+        }
+        copy_dir_all(datapack, &target)
+    } else {
+        fs::copy(datapack, target).map(|_| ())
+    }
+}
+
+/// Returns the names of the enabled datapacks from the output of `datapack list`
+///
+/// The output looks like `There are 2 data pack(s) enabled: [vanilla (built-in)], [file/test (world)]`
+/// or `There are no data packs enabled`. Returns None for any other line.
+pub fn parse_enabled_datapacks(line: &str) -> Option<Vec<String>> {
+    let message = line.split_once("]: ").map_or(line, |(_, message)| message);
+    let message = message.trim_end();
+    if message == "There are no data packs enabled" {
+        return Some(Vec::new());
+    }
+
+    let (count, list) = message
+        .strip_prefix("There are ")?
+        .split_once(" data pack(s) enabled: ")?;
+    let entries = parse_bracketed_list(list)?;
+    if count.parse::<usize>().ok()? != entries.len() {
+        return None;
+    }
+
+    let names = entries
+        .into_iter()
+        .map(|entry| {
+            // Strips the source, like `(world)` or `(built-in)`
+            let name = entry
+                .rsplit_once(" (")
+                .filter(|(_, source)| source.ends_with(')'))
+                .map_or(entry, |(name, _)| name);
+            name.to_string()
+        })
+        .collect();
+    Some(names)
+}
+
+/// Splits a list like `[a], [b]` into its entries, which may contain brackets and commas themselves
+fn parse_bracketed_list(mut list: &str) -> Option<Vec<&str>> {
+    let mut entries = Vec::new();
+    loop {
+        list = list.strip_prefix('[')?;
+
+        let mut depth = 1;
+        let end = list.char_indices().find_map(|(index, c)| {
+            match c {
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                _ => (),
+            }
+            (depth == 0).then_some(index)
+        })?;
+        entries.push(&list[..end]);
+
+        list = &list[end + 1..];
+        if list.is_empty() {
+            return Some(entries);
+        }
+        list = list.strip_prefix(", ")?;
+    }
+}
+
+impl ServerInstance {
+    /// Installs a datapack folder or zip file into the world of the running server and reloads
+    ///
+    /// Returns [`InstanceError::DatapackNotEnabled`] with the errors and warnings logged during
+    /// the reload if the datapack could not be enabled.
+    pub fn install_datapack(&mut self, datapack: impl AsRef<Path>) -> Result<()> {
+        let datapack = datapack.as_ref();
+        let name = format!("file/{}", datapack_file_name(datapack)?);
+        install_datapack_files(datapack, self.dir.join(&self.world_name))?;
+
+        let log_start = self.log().len();
+        self.command("reload")?;
+
+        // The reload happens asynchronously, so poll until the datapack shows up
+        let deadline = Instant::now() + ENABLE_TIMEOUT;
+        while Instant::now() < deadline {
+            if self.enabled_datapacks()?.contains(&name) {
+                return Ok(());
+            }
+            thread::sleep(LIST_INTERVAL);
+        }
+
+        let errors = self
+            .log()
+            .lines_since(log_start)
+            .into_iter()
+            .filter(|line| line.contains("/ERROR]") || line.contains("/WARN]"))
+            .collect();
+        Err(InstanceError::DatapackNotEnabled { name, errors })
+    }
+
+    /// Returns the names of the enabled datapacks, as reported by `datapack list`
+    pub fn enabled_datapacks(&mut self) -> Result<Vec<String>> {
+        let log_start = self.log().len();
+        self.command("datapack list")?;
+
+        let index = self
+            .log()
+            .wait_for(log_start, Some(LIST_TIMEOUT), |line| {
+                parse_enabled_datapacks(line).is_some()
+            })
+            .ok_or_else(|| {
+                InstanceError::Other("The server did not list its datapacks".to_string())
+            })?;
+        let line = &self.log().lines_since(index)[0];
+        Ok(parse_enabled_datapacks(line).unwrap_or_default())
+    }
+}
+
+fn datapack_file_name(datapack: &Path) -> io::Result<&str> {
+    datapack
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid datapack path"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{install_datapack_files, parse_enabled_datapacks};
+
+    #[test]
+    fn test_parse_enabled_datapacks() {
+        let line = "[12:00:00] [Server thread/INFO]: There are 2 data pack(s) enabled: [vanilla (built-in)], [file/my pack.zip (world)]";
+        assert_eq!(
+            parse_enabled_datapacks(line),
+            Some(vec!["vanilla".to_string(), "file/my pack.zip".to_string()])
+        );
+
+        let line = "[12:00:00] [Server thread/INFO]: There are no data packs enabled";
+        assert_eq!(parse_enabled_datapacks(line), Some(Vec::new()));
+
+        let line = "[12:00:00] [Server thread/INFO]: There are 2 data pack(s) enabled: [file/a, b [1] (world)], [vanilla (built-in)]";
+        assert_eq!(
+            parse_enabled_datapacks(line),
+            Some(vec!["file/a, b [1]".to_string(), "vanilla".to_string()])
+        );
+
+        let line = "[12:00:00] [Server thread/INFO]: There are 1 data pack(s) available: [file/other (world)]";
+        assert_eq!(parse_enabled_datapacks(line), None);
+
+        // Chat messages must not be mistaken for the list
+        let line =
+            "[12:00:00] [Server thread/INFO]: <Steve> data pack enabled: [file/fake (world)]";
+        assert_eq!(parse_enabled_datapacks(line), None);
+    }
+
+    #[test]
+    fn test_install_datapack_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let datapack = dir.path().join("my_pack");
+        fs::create_dir_all(datapack.join("data")).unwrap();
+        fs::write(datapack.join("pack.mcmeta"), "{}").unwrap();
+
+        let world = dir.path().join("world");
+        install_datapack_files(&datapack, &world).unwrap();
+
+        assert!(world.join("datapacks/my_pack/pack.mcmeta").is_file());
+        assert!(world.join("datapacks/my_pack/data").is_dir());
+    }
+}
@@ -0,0 +1,395 @@
+//! The typed model of the per-version json, which is linked from the version manifest
+use std::{collections::HashMap, io::Read};
+
+use serde::Deserialize;
+
+use crate::{
+    download::Checksum,
+    version::{deserialize_time, ManifestError, VersionDownload, VersionType},
+};
+
+/// The complete metadata of a single minecraft version
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionDetails {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub typ: VersionType,
+    pub main_class: Option<String>,
+    pub downloads: Downloads,
+    pub java_version: Option<JavaVersion>,
+    pub asset_index: Option<AssetIndex>,
+    /// The id of the asset index
+    pub assets: Option<String>,
+    #[serde(default)]
+    pub libraries: Vec<Library>,
+    /// The launch arguments of versions since 1.13
+    pub arguments: Option<Arguments>,
+    /// The space separated game arguments of versions before 1.13
+    pub minecraft_arguments: Option<String>,
+    pub logging: Option<Logging>,
+    pub compliance_level: Option<u32>,
+    #[serde(deserialize_with = "deserialize_time")]
+    pub release_time: i64,
+    #[serde(default, deserialize_with = "deserialize_time")]
+    pub time: i64,
+}
+
+impl VersionDetails {
+    pub fn from_reader(reader: impl Read) -> Result<Self, ManifestError> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+/// The jars and mappings of a version, very old versions have no server
+#[derive(Debug, Clone, Deserialize)]
+pub struct Downloads {
+    pub client: Option<Download>,
+    pub server: Option<Download>,
+    pub client_mappings: Option<Download>,
+    pub server_mappings: Option<Download>,
+}
+
+/// A downloadable file with its published hash and size
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Download {
+    pub sha1: String,
+    pub size: u64,
+    pub url: String,
+}
+
+impl Download {
+    pub fn checksum(&self) -> Checksum {
+        Checksum {
+            sha1: self.sha1.clone(),
+            size: Some(self.size),
+        }
+    }
+}
+
+impl From<Download> for VersionDownload {
+    fn from(download: Download) -> Self {
+        VersionDownload {
+            checksum: download.checksum(),
+            url: download.url,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JavaVersion {
+    /// The name of the java runtime distributed by mojang, e.g. `java-runtime-gamma`
+    pub component: String,
+    pub major_version: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetIndex {
+    pub id: String,
+    pub sha1: String,
+    pub size: u64,
+    /// The size of all assets listed in the index
+    pub total_size: Option<u64>,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Library {
+    /// The maven coordinates of the library
+    pub name: String,
+    pub downloads: Option<LibraryDownloads>,
+    /// The library is only used if the rules allow it
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// Maps the os name to the classifier of the native library
+    pub natives: Option<HashMap<String, String>>,
+}
+
+impl Library {
+    pub fn is_allowed(&self, context: &RuleContext) -> bool {
+        rules_allow(&self.rules, context)
+    }
+
+    /// Returns the artifacts needed on the platform of 'context', the jar and the native library
+    ///
+    /// Returns nothing if the rules do not allow the library.
+    pub fn artifacts(&self, context: &RuleContext) -> Vec<&Artifact> {
+        let downloads = match &self.downloads {
+            Some(downloads) if self.is_allowed(context) => downloads,
+            _ => return Vec::new(),
+        };
+
+        // `${arch}` is the bitness of the target platform, e.g. `natives-windows-64`
+        let bits = if context.arch.ends_with("64") {
+            "64"
+        } else {
+            "32"
+        };
+        let native = self
+            .natives
+            .as_ref()
+            .and_then(|natives| natives.get(&context.os_name))
+            .map(|classifier| classifier.replace("${arch}", bits))
+            .and_then(|classifier| downloads.classifiers.as_ref()?.get(&classifier));
+
+        downloads.artifact.iter().chain(native).collect()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LibraryDownloads {
+    pub artifact: Option<Artifact>,
+    /// Native libraries of versions before 1.19, keyed by their classifier
+    pub classifiers: Option<HashMap<String, Artifact>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Artifact {
+    /// The path of the artifact in a maven repository layout
+    pub path: String,
+    pub sha1: String,
+    pub size: u64,
+    pub url: String,
+}
+
+/// A condition for libraries and arguments
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Rule {
+    pub action: RuleAction,
+    pub os: Option<OsRule>,
+    /// Launcher features like `is_demo_user`, which must have the given value
+    pub features: Option<HashMap<String, bool>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleAction {
+    Allow,
+    Disallow,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct OsRule {
+    /// One of `linux`, `osx` and `windows`
+    pub name: Option<String>,
+    /// A regex which matches the os version
+    pub version: Option<String>,
+    pub arch: Option<String>,
+}
+
+impl Rule {
+    /// Whether the os and the features of this rule match 'context'
+    ///
+    /// The os version is not checked, since it is a regex.
+    pub fn matches(&self, context: &RuleContext) -> bool {
+        let os_matches = self.os.as_ref().is_none_or(|os| {
+            os.name.as_ref().is_none_or(|name| *name == context.os_name)
+                && os.arch.as_ref().is_none_or(|arch| *arch == context.arch)
+        });
+        let features_match = self.features.as_ref().is_none_or(|features| {
+            features.iter().all(|(feature, value)| {
+                context.features.get(feature).copied().unwrap_or(false) == *value
+            })
+        });
+        os_matches && features_match
+    }
+}
+
+/// Evaluates 'rules' like the launcher: without rules everything is allowed,
+/// otherwise the last matching rule decides
+pub fn rules_allow(rules: &[Rule], context: &RuleContext) -> bool {
+    if rules.is_empty() {
+        return true;
+    }
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.matches(context))
+        .is_some_and(|rule| rule.action == RuleAction::Allow)
+}
+
+/// The platform and launcher features which rules are evaluated against
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleContext {
+    /// One of `linux`, `osx` and `windows`
+    pub os_name: String,
+    pub arch: String,
+    pub features: HashMap<String, bool>,
+}
+
+impl RuleContext {
+    /// Linux on the current architecture without any launcher features
+    pub fn linux() -> Self {
+        RuleContext {
+            os_name: "linux".to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            features: HashMap::new(),
+        }
+    }
+}
+
+impl Default for RuleContext {
+    fn default() -> Self {
+        RuleContext::linux()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Arguments {
+    #[serde(default)]
+    pub game: Vec<Argument>,
+    #[serde(default)]
+    pub jvm: Vec<Argument>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Argument {
+    Plain(String),
+    Conditional {
+        rules: Vec<Rule>,
+        value: ArgumentValue,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum ArgumentValue {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Logging {
+    pub client: Option<LoggingConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoggingConfig {
+    /// The jvm argument which enables the config, with `${path}` as placeholder for the file
+    pub argument: String,
+    pub file: LoggingFile,
+    #[serde(rename = "type")]
+    pub typ: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoggingFile {
+    pub id: String,
+    pub sha1: String,
+    pub size: u64,
+    pub url: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Argument, ArgumentValue, Library, RuleAction, RuleContext, VersionDetails};
+    use crate::version::VersionType;
+
+    /// A shortened version json of 1.20.4
+    const VERSION_JSON: &str = r#"{
+        "arguments": {
+            "game": [
+                "--username",
+                "${auth_player_name}",
+                {"rules": [{"action": "allow", "features": {"is_demo_user": true}}], "value": "--demo"}
+            ],
+            "jvm": [
+                {"rules": [{"action": "allow", "os": {"name": "osx"}}], "value": ["-XstartOnFirstThread"]},
+                "-cp",
+                "${classpath}"
+            ]
+        },
+        "assetIndex": {"id": "12", "sha1": "aaa", "size": 412, "totalSize": 624, "url": "https://example.com/12.json"},
+        "assets": "12",
+        "complianceLevel": 1,
+        "downloads": {
+            "client": {"sha1": "bbb", "size": 2, "url": "https://example.com/client.jar"},
+            "server": {"sha1": "ccc", "size": 3, "url": "https://example.com/server.jar"},
+            "server_mappings": {"sha1": "ddd", "size": 4, "url": "https://example.com/server.txt"}
+        },
+        "id": "1.20.4",
+        "javaVersion": {"component": "java-runtime-gamma", "majorVersion": 17},
+        "libraries": [
+            {
+                "downloads": {"artifact": {"path": "org/lwjgl/lwjgl/3.3.2/lwjgl-3.3.2-natives-linux.jar", "sha1": "eee", "size": 5, "url": "https://example.com/lwjgl.jar"}},
+                "name": "org.lwjgl:lwjgl:3.3.2:natives-linux",
+                "rules": [{"action": "allow", "os": {"name": "linux"}}]
+            }
+        ],
+        "logging": {
+            "client": {
+                "argument": "-Dlog4j.configurationFile=${path}",
+                "file": {"id": "client-1.12.xml", "sha1": "fff", "size": 6, "url": "https://example.com/client.xml"},
+                "type": "log4j2-xml"
+            }
+        },
+        "mainClass": "net.minecraft.client.main.Main",
+        "releaseTime": "2023-12-07T12:56:20+00:00",
+        "time": "2023-12-07T12:56:20+00:00",
+        "type": "release"
+    }"#;
+
+    #[test]
+    fn test_parse_version_details() {
+        let details = VersionDetails::from_reader(VERSION_JSON.as_bytes()).unwrap();
+
+        assert_eq!(details.id, "1.20.4");
+        assert_eq!(details.typ, VersionType::Release);
+        assert_eq!(details.java_version.unwrap().major_version, 17);
+        assert_eq!(details.asset_index.unwrap().total_size, Some(624));
+        assert_eq!(details.downloads.server.unwrap().sha1, "ccc");
+        assert!(details.downloads.client_mappings.is_none());
+        assert_eq!(details.libraries[0].rules[0].action, RuleAction::Allow);
+        assert_eq!(details.logging.unwrap().client.unwrap().typ, "log4j2-xml");
+        assert_eq!(details.release_time, 1701953780);
+
+        let arguments = details.arguments.unwrap();
+        assert_eq!(arguments.game[0], Argument::Plain("--username".to_string()));
+        assert!(matches!(
+            &arguments.jvm[0],
+            Argument::Conditional { value: ArgumentValue::Multiple(values), .. } if values == &["-XstartOnFirstThread"]
+        ));
+    }
+
+    #[test]
+    fn test_library_rules() {
+        let details = VersionDetails::from_reader(VERSION_JSON.as_bytes()).unwrap();
+        let library = &details.libraries[0];
+
+        let linux = RuleContext::linux();
+        assert_eq!(library.artifacts(&linux).len(), 1);
+
+        let osx = RuleContext {
+            os_name: "osx".to_string(),
+            ..RuleContext::linux()
+        };
+        assert!(!library.is_allowed(&osx));
+        assert!(library.artifacts(&osx).is_empty());
+    }
+
+    #[test]
+    fn test_native_arch() {
+        let library: Library = serde_json::from_str(
+            r#"{
+                "name": "tv.twitch:twitch-platform:5.16:natives-windows-${arch}",
+                "downloads": {"classifiers": {
+                    "natives-windows-32": {"path": "32.jar", "sha1": "aaa", "size": 1, "url": "https://example.com/32.jar"},
+                    "natives-windows-64": {"path": "64.jar", "sha1": "bbb", "size": 1, "url": "https://example.com/64.jar"}
+                }},
+                "natives": {"windows": "natives-windows-${arch}"}
+            }"#,
+        )
+        .unwrap();
+
+        let windows = |arch: &str| RuleContext {
+            os_name: "windows".to_string(),
+            arch: arch.to_string(),
+            ..RuleContext::linux()
+        };
+        assert_eq!(library.artifacts(&windows("x86"))[0].path, "32.jar");
+        assert_eq!(library.artifacts(&windows("x86_64"))[0].path, "64.jar");
+    }
+}
@@ -0,0 +1,275 @@
+use std::{
+    ffi::{OsStr, OsString},
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use super::{run_installer, ServerDistribution};
+use crate::{download::download_file, http::HttpClient};
+
+pub const FABRIC_META_URL: &str = "https://meta.fabricmc.net/v2";
+pub const QUILT_META_URL: &str = "https://meta.quiltmc.org/v3";
+pub const QUILT_MAVEN_URL: &str = "https://maven.quiltmc.org/repository/release";
+
+/// The fabric server launcher, which downloads the vanilla server and the loader on first start
+///
+/// The latest stable loader and installer are used unless specific versions are set.
+#[derive(Debug, Clone)]
+pub struct Fabric {
+    pub meta_url: String,
+    pub loader_version: Option<String>,
+    pub installer_version: Option<String>,
+    pub client: HttpClient,
+}
+
+impl Default for Fabric {
+    fn default() -> Self {
+        Fabric {
+            meta_url: FABRIC_META_URL.to_string(),
+            loader_version: None,
+            installer_version: None,
+            client: HttpClient::default(),
+        }
+    }
+}
+
+impl ServerDistribution for Fabric {
+    fn install(&self, version: &str, dir: &Path) -> io::Result<PathBuf> {
+        install_launcher(
+            &self.client,
+            &self.meta_url,
+            version,
+            self.loader_version.as_deref(),
+            self.installer_version.as_deref(),
+            &dir.join("fabric-server-launch.jar"),
+        )
+    }
+}
+
+/// A quilt server, installed by running `install server` of the quilt installer
+///
+/// Quilt has no server launcher download, the installer downloads the vanilla server
+/// and creates the `quilt-server-launch.jar`. The loader versions are taken from the meta api,
+/// the installer from the maven repository. The latest stable versions are used unless
+/// specific versions are set.
+#[derive(Debug, Clone)]
+pub struct Quilt {
+    pub meta_url: String,
+    pub maven_url: String,
+    pub loader_version: Option<String>,
+    pub installer_version: Option<String>,
+    pub client: HttpClient,
+}
+
+impl Default for Quilt {
+    fn default() -> Self {
+        Quilt {
+            meta_url: QUILT_META_URL.to_string(),
+            maven_url: QUILT_MAVEN_URL.to_string(),
+            loader_version: None,
+            installer_version: None,
+            client: HttpClient::default(),
+        }
+    }
+}
+
+impl Quilt {
+    /// Resolves the loader version for 'version' and the url of the installer
+    fn resolve(&self, version: &str) -> io::Result<(String, String)> {
+        let loader_version = match &self.loader_version {
+            Some(loader_version) => loader_version.clone(),
+            None => latest_loader(&self.client, &self.meta_url, version)?,
+        };
+
+        let installer_version = match &self.installer_version {
+            Some(installer_version) => installer_version.clone(),
+            None => {
+                let metadata = self.client.get_bytes(&format!(
+                    "{}/org/quiltmc/quilt-installer/maven-metadata.xml",
+                    self.maven_url
+                ))?;
+                maven_release(&String::from_utf8_lossy(&metadata)).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, "No quilt installer available")
+                })?
+            }
+        };
+
+        let url = format!(
+            "{}/org/quiltmc/quilt-installer/{}/quilt-installer-{}.jar",
+            self.maven_url, installer_version, installer_version
+        );
+        Ok((loader_version, url))
+    }
+}
+
+impl ServerDistribution for Quilt {
+    fn install(&self, version: &str, dir: &Path) -> io::Result<PathBuf> {
+        let (loader_version, url) = self.resolve(version)?;
+        let mut install_dir = OsString::from("--install-dir=");
+        install_dir.push(dir);
+        run_installer(
+            &self.client,
+            &url,
+            dir,
+            "quilt-installer.jar",
+            [
+                OsStr::new("install"),
+                OsStr::new("server"),
+                OsStr::new(version),
+                OsStr::new(&loader_version),
+                OsStr::new("--download-server"),
+                &install_dir,
+            ],
+        )?;
+
+        let launcher = dir.join("quilt-server-launch.jar");
+        if !launcher.is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "The installer did not create quilt-server-launch.jar",
+            ));
+        }
+        Ok(launcher)
+    }
+}
+
+/// Reads the version in `<release>` of a `maven-metadata.xml`
+fn maven_release(metadata: &str) -> Option<String> {
+    let (_, rest) = metadata.split_once("<release>")?;
+    let (release, _) = rest.split_once("</release>")?;
+    Some(release.trim().to_string())
+}
+
+#[derive(Debug, Deserialize)]
+struct LoaderEntry {
+    loader: MetaVersion,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetaVersion {
+    version: String,
+    /// Quilt does not mark its versions as stable
+    stable: Option<bool>,
+}
+
+impl MetaVersion {
+    /// Versions without a stable flag are stable unless they are pre-releases like `0.26.0-beta.1`
+    fn is_stable(&self) -> bool {
+        self.stable.unwrap_or_else(|| !self.version.contains('-'))
+    }
+}
+
+fn install_launcher(
+    client: &HttpClient,
+    meta_url: &str,
+    version: &str,
+    loader_version: Option<&str>,
+    installer_version: Option<&str>,
+    destination: &Path,
+) -> io::Result<PathBuf> {
+    let loader_version = match loader_version {
+        Some(loader_version) => loader_version.to_string(),
+        None => latest_loader(client, meta_url, version)?,
+    };
+
+    let installer_version = match installer_version {
+        Some(installer_version) => installer_version.to_string(),
+        None => {
+            let installers: Vec<MetaVersion> =
+                client.get_json(&format!("{}/versions/installer", meta_url))?;
+            latest_stable(installers)?
+        }
+    };
+
+    let url = format!(
+        "{}/versions/loader/{}/{}/{}/server/jar",
+        meta_url, version, loader_version, installer_version
+    );
+    download_file(client, &url, destination)?;
+    Ok(destination.to_path_buf())
+}
+
+/// Returns the latest stable loader for the minecraft version 'version'
+fn latest_loader(client: &HttpClient, meta_url: &str, version: &str) -> io::Result<String> {
+    let loaders: Vec<LoaderEntry> =
+        client.get_json(&format!("{}/versions/loader/{}", meta_url, version))?;
+    latest_stable(loaders.into_iter().map(|entry| entry.loader))
+}
+
+/// Returns the first stable version, the meta api lists the newest versions first
+fn latest_stable(versions: impl IntoIterator<Item = MetaVersion>) -> io::Result<String> {
+    versions
+        .into_iter()
+        .find(MetaVersion::is_stable)
+        .map(|version| version.version)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No stable version available"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{Fabric, Quilt};
+    use crate::{
+        distribution::ServerDistribution,
+        http::{HttpClient, StubBackend},
+        test_util::serve,
+    };
+
+    #[test]
+    fn test_install_fabric() {
+        let url = serve(vec![
+            (
+                "/versions/loader/1.20.4",
+                br#"[{"loader": {"version": "0.16.0-beta", "stable": false}}, {"loader": {"version": "0.15.6", "stable": true}}]"#.to_vec(),
+            ),
+            (
+                "/versions/installer",
+                br#"[{"version": "1.0.0", "stable": true}]"#.to_vec(),
+            ),
+            (
+                "/versions/loader/1.20.4/0.15.6/1.0.0/server/jar",
+                b"launcher".to_vec(),
+            ),
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+
+        let fabric = Fabric {
+            meta_url: url,
+            ..Fabric::default()
+        };
+        let jar = fabric.install("1.20.4", dir.path()).unwrap();
+
+        assert_eq!(fs::read(jar).unwrap(), b"launcher");
+    }
+
+    #[test]
+    fn test_resolve_quilt() {
+        let backend = StubBackend::new()
+            .route(
+                "http://stub/meta/versions/loader/1.20.4",
+                r#"[{"loader": {"version": "0.26.0-beta.1"}}, {"loader": {"version": "0.25.0"}}]"#,
+            )
+            .route(
+                "http://stub/maven/org/quiltmc/quilt-installer/maven-metadata.xml",
+                "<metadata><versioning><latest>0.9.2</latest><release>0.9.2</release></versioning></metadata>",
+            );
+        let quilt = Quilt {
+            meta_url: "http://stub/meta".to_string(),
+            maven_url: "http://stub/maven".to_string(),
+            client: HttpClient::with_backend(backend),
+            ..Quilt::default()
+        };
+
+        assert_eq!(
+            quilt.resolve("1.20.4").unwrap(),
+            (
+                "0.25.0".to_string(),
+                "http://stub/maven/org/quiltmc/quilt-installer/0.9.2/quilt-installer-0.9.2.jar"
+                    .to_string()
+            )
+        );
+    }
+}
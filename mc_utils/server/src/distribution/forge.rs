@@ -0,0 +1,310 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use super::{run_installer, ServerDistribution};
+use crate::http::HttpClient;
+
+pub const FORGE_MAVEN_URL: &str = "https://maven.minecraftforge.net";
+pub const FORGE_PROMOTIONS_URL: &str =
+    "https://files.minecraftforge.net/net/minecraftforge/forge/promotions_slim.json";
+pub const NEOFORGE_MAVEN_URL: &str = "https://maven.neoforged.net";
+
+/// A forge server, installed by running the forge installer
+///
+/// The recommended forge version for the minecraft version is used unless a specific version is set.
+#[derive(Debug, Clone)]
+pub struct Forge {
+    pub maven_url: String,
+    pub promotions_url: String,
+    pub forge_version: Option<String>,
+    pub client: HttpClient,
+}
+
+impl Default for Forge {
+    fn default() -> Self {
+        Forge {
+            maven_url: FORGE_MAVEN_URL.to_string(),
+            promotions_url: FORGE_PROMOTIONS_URL.to_string(),
+            forge_version: None,
+            client: HttpClient::default(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Promotions {
+    promos: HashMap<String, String>,
+}
+
+impl Forge {
+    /// Resolves the forge version for 'version' and returns the url of its installer
+    fn installer_url(&self, version: &str) -> io::Result<String> {
+        let forge_version = match &self.forge_version {
+            Some(forge_version) => forge_version.clone(),
+            None => {
+                let mut promotions: Promotions = self.client.get_json(&self.promotions_url)?;
+                let recommended = promotions
+                    .promos
+                    .remove(&format!("{}-recommended", version));
+                recommended
+                    .or_else(|| promotions.promos.remove(&format!("{}-latest", version)))
+                    .ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::NotFound, "No forge version available")
+                    })?
+            }
+        };
+
+        let full_version = format!("{}-{}", version, forge_version);
+        Ok(format!(
+            "{}/net/minecraftforge/forge/{}/forge-{}-installer.jar",
+            self.maven_url, full_version, full_version
+        ))
+    }
+}
+
+impl ServerDistribution for Forge {
+    fn install(&self, version: &str, dir: &Path) -> io::Result<PathBuf> {
+        let url = self.installer_url(version)?;
+        install_server(&self.client, &url, dir, "forge-")
+    }
+}
+
+/// A neoforge server, installed by running the neoforge installer
+///
+/// The latest neoforge version for the minecraft version is used unless a specific version is set.
+#[derive(Debug, Clone)]
+pub struct NeoForge {
+    pub maven_url: String,
+    pub neoforge_version: Option<String>,
+    pub client: HttpClient,
+}
+
+impl Default for NeoForge {
+    fn default() -> Self {
+        NeoForge {
+            maven_url: NEOFORGE_MAVEN_URL.to_string(),
+            neoforge_version: None,
+            client: HttpClient::default(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MavenVersions {
+    versions: Vec<String>,
+}
+
+impl NeoForge {
+    /// Resolves the neoforge version for 'version' and returns the url of its installer
+    fn installer_url(&self, version: &str) -> io::Result<String> {
+        let neoforge_version = match &self.neoforge_version {
+            Some(neoforge_version) => neoforge_version.clone(),
+            None => {
+                // Neoforge versions drop the leading `1.` of the minecraft version, e.g. 20.4.x for 1.20.4
+                let prefix = neoforge_prefix(version);
+                let versions: MavenVersions = self.client.get_json(&format!(
+                    "{}/api/maven/versions/releases/net/neoforged/neoforge",
+                    self.maven_url
+                ))?;
+                versions
+                    .versions
+                    .into_iter()
+                    .rev()
+                    .find(|neoforge_version| {
+                        neoforge_version.starts_with(&prefix) && !neoforge_version.contains("-beta")
+                    })
+                    .ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::NotFound, "No neoforge version available")
+                    })?
+            }
+        };
+
+        Ok(format!(
+            "{}/releases/net/neoforged/neoforge/{}/neoforge-{}-installer.jar",
+            self.maven_url, neoforge_version, neoforge_version
+        ))
+    }
+}
+
+impl ServerDistribution for NeoForge {
+    fn install(&self, version: &str, dir: &Path) -> io::Result<PathBuf> {
+        let url = self.installer_url(version)?;
+        install_server(&self.client, &url, dir, "neoforge-")
+    }
+}
+
+/// Converts a minecraft version like `1.20.4` to the neoforge version prefix `20.4.`
+fn neoforge_prefix(version: &str) -> String {
+    let version = version.strip_prefix("1.").unwrap_or(version);
+    if version.contains('.') {
+        format!("{}.", version)
+    } else {
+        format!("{}.0.", version)
+    }
+}
+
+/// Downloads the installer from 'url', installs the server into 'dir' and returns the launch file
+fn install_server(
+    client: &HttpClient,
+    url: &str,
+    dir: &Path,
+    jar_prefix: &str,
+) -> io::Result<PathBuf> {
+    let installer = format!("{}installer.jar", jar_prefix);
+    run_installer(
+        client,
+        url,
+        dir,
+        &installer,
+        [OsStr::new("--installServer"), dir.as_os_str()],
+    )?;
+    find_launch_file(dir, jar_prefix)
+}
+
+/// Finds the file created by the installer which starts the server
+///
+/// This is either a shim jar, the universal jar or, for forge 1.17 to 1.20.2 which is started
+/// by `run.sh`, the `unix_args.txt` args file, which [`run_server`](crate::run_server) accepts too.
+fn find_launch_file(dir: &Path, jar_prefix: &str) -> io::Result<PathBuf> {
+    let mut jars = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_launch_jar = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| {
+                name.starts_with(jar_prefix)
+                    && name.ends_with(".jar")
+                    && !name.ends_with("-installer.jar")
+            });
+        if is_launch_jar {
+            jars.push(path);
+        }
+    }
+
+    jars.sort_unstable_by_key(|jar| !jar.to_string_lossy().ends_with("-shim.jar"));
+    if let Some(jar) = jars.into_iter().next() {
+        return Ok(jar);
+    }
+
+    find_args_file(dir)?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "The installer did not create a launchable jar",
+        )
+    })
+}
+
+/// Finds the `unix_args.txt` referenced by the `run.sh` created by the installer
+///
+/// The script contains a line like `java @user_jvm_args.txt @libraries/net/minecraftforge/forge/1.20.1-47.2.0/unix_args.txt "$@"`.
+fn find_args_file(dir: &Path) -> io::Result<Option<PathBuf>> {
+    let script = match fs::read_to_string(dir.join("run.sh")) {
+        Ok(script) => script,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error),
+    };
+
+    let args_file = script
+        .split_whitespace()
+        .filter_map(|arg| arg.strip_prefix('@'))
+        .find(|arg| arg.ends_with("unix_args.txt"))
+        .map(|arg| dir.join(arg));
+    Ok(args_file.filter(|path| path.is_file()))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{find_launch_file, neoforge_prefix, Forge, NeoForge};
+    use crate::http::{HttpClient, StubBackend};
+
+    #[test]
+    fn test_neoforge_prefix() {
+        assert_eq!(neoforge_prefix("1.20.4"), "20.4.");
+        assert_eq!(neoforge_prefix("1.21"), "21.0.");
+    }
+
+    #[test]
+    fn test_forge_installer_url() {
+        let backend = StubBackend::new().route(
+            "http://stub/promotions_slim.json",
+            r#"{"promos": {"1.20.1-latest": "47.2.20", "1.20.1-recommended": "47.2.0", "1.20.4-latest": "49.0.3"}}"#,
+        );
+        let forge = Forge {
+            maven_url: "http://stub".to_string(),
+            promotions_url: "http://stub/promotions_slim.json".to_string(),
+            forge_version: None,
+            client: HttpClient::with_backend(backend),
+        };
+
+        assert_eq!(
+            forge.installer_url("1.20.1").unwrap(),
+            "http://stub/net/minecraftforge/forge/1.20.1-47.2.0/forge-1.20.1-47.2.0-installer.jar"
+        );
+        assert_eq!(
+            forge.installer_url("1.20.4").unwrap(),
+            "http://stub/net/minecraftforge/forge/1.20.4-49.0.3/forge-1.20.4-49.0.3-installer.jar"
+        );
+        assert!(forge.installer_url("1.12").is_err());
+    }
+
+    #[test]
+    fn test_neoforge_installer_url() {
+        let backend = StubBackend::new().route(
+            "http://stub/api/maven/versions/releases/net/neoforged/neoforge",
+            r#"{"versions": ["20.4.80-beta", "20.4.237", "20.4.238-beta", "20.6.1-beta"]}"#,
+        );
+        let neoforge = NeoForge {
+            maven_url: "http://stub".to_string(),
+            neoforge_version: None,
+            client: HttpClient::with_backend(backend),
+        };
+
+        assert_eq!(
+            neoforge.installer_url("1.20.4").unwrap(),
+            "http://stub/releases/net/neoforged/neoforge/20.4.237/neoforge-20.4.237-installer.jar"
+        );
+        assert!(neoforge.installer_url("1.20.6").is_err());
+    }
+
+    #[test]
+    fn test_find_launch_jar() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("forge-1.20.4-49.0.3-installer.jar"), "").unwrap();
+        fs::write(dir.path().join("forge-1.20.4-49.0.3.jar"), "").unwrap();
+        fs::write(dir.path().join("forge-1.20.4-49.0.3-shim.jar"), "").unwrap();
+
+        assert_eq!(
+            find_launch_file(dir.path(), "forge-").unwrap(),
+            dir.path().join("forge-1.20.4-49.0.3-shim.jar")
+        );
+    }
+
+    #[test]
+    fn test_find_args_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let args_dir = dir
+            .path()
+            .join("libraries/net/minecraftforge/forge/1.20.1-47.2.0");
+        fs::create_dir_all(&args_dir).unwrap();
+        fs::write(args_dir.join("unix_args.txt"), "").unwrap();
+        fs::write(
+            dir.path().join("run.sh"),
+            "#!/usr/bin/env sh\njava @user_jvm_args.txt @libraries/net/minecraftforge/forge/1.20.1-47.2.0/unix_args.txt \"$@\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            find_launch_file(dir.path(), "forge-").unwrap(),
+            args_dir.join("unix_args.txt")
+        );
+    }
+}
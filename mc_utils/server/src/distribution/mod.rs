@@ -0,0 +1,139 @@
+//! Installers for vanilla and modded server distributions
+//!
+//! Every distribution downloads from a base url which can be overridden,
+//! so that mirrors or local test servers can be used, and through its own [`HttpClient`].
+use std::{
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::{
+    download::download_file, download_verified, HttpClient, VersionManifest,
+    VERSION_MANIFEST_V2_URL,
+};
+
+mod fabric;
+pub use fabric::{Fabric, Quilt, FABRIC_META_URL, QUILT_MAVEN_URL, QUILT_META_URL};
+
+mod forge;
+pub use forge::{Forge, NeoForge, FORGE_MAVEN_URL, FORGE_PROMOTIONS_URL, NEOFORGE_MAVEN_URL};
+
+mod paper;
+pub use paper::{Paper, Purpur, PAPER_API_URL, PURPUR_API_URL};
+
+/// A server software which can be installed for a minecraft version
+pub trait ServerDistribution {
+    /// Installs the server for the minecraft version 'version' into 'dir'
+    ///
+    /// Returns the path of the jar which starts the server, or of the args file for forge 1.17 to 1.20.2.
+    /// Both can be passed to [`ServerInstance::with_jar`](crate::ServerInstance::with_jar).
+    fn install(&self, version: &str, dir: &Path) -> io::Result<PathBuf>;
+}
+
+/// The vanilla server from the mojang version manifest
+#[derive(Debug, Clone)]
+pub struct Vanilla {
+    pub manifest_url: String,
+    pub client: HttpClient,
+}
+
+impl Default for Vanilla {
+    fn default() -> Self {
+        Vanilla {
+            manifest_url: VERSION_MANIFEST_V2_URL.to_string(),
+            client: HttpClient::default(),
+        }
+    }
+}
+
+impl ServerDistribution for Vanilla {
+    fn install(&self, version: &str, dir: &Path) -> io::Result<PathBuf> {
+        let manifest = VersionManifest::from_url(&self.client, &self.manifest_url)?;
+        let info = manifest.find_version(version).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "Could not find the given version")
+        })?;
+        let server = info
+            .details(&self.client)?
+            .downloads
+            .server
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "The version has no server download",
+                )
+            })?;
+
+        let jar = dir.join("server.jar");
+        download_verified(&self.client, &server.url, &jar, &server.checksum())?;
+        Ok(jar)
+    }
+}
+
+/// Downloads the installer jar from 'url' to 'dir/name', runs it in 'dir' with 'args' and removes it
+fn run_installer(
+    client: &HttpClient,
+    url: &str,
+    dir: &Path,
+    name: &str,
+    args: impl IntoIterator<Item = impl AsRef<OsStr>>,
+) -> io::Result<()> {
+    let installer = dir.join(name);
+    download_file(client, url, &installer)?;
+
+    let status = Command::new("java")
+        .arg("-jar")
+        .arg(&installer)
+        .args(args)
+        .current_dir(dir)
+        .stdout(Stdio::null())
+        .status()?;
+    fs::remove_file(&installer)?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "Installer did not exit successfully: {}",
+            status
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{ServerDistribution, Vanilla};
+    use crate::http::{HttpClient, StubBackend};
+
+    #[test]
+    fn test_install_vanilla() {
+        let backend = StubBackend::new()
+            .route(
+                "http://stub/manifest.json",
+                r#"{
+                    "latest": {"release": "1.20.4", "snapshot": "1.20.4"},
+                    "versions": [{"id": "1.20.4", "type": "release", "url": "http://stub/1.20.4.json", "releaseTime": "2023-12-07T12:56:20+00:00"}]
+                }"#,
+            )
+            .route(
+                "http://stub/1.20.4.json",
+                r#"{
+                    "id": "1.20.4",
+                    "type": "release",
+                    "releaseTime": "2023-12-07T12:56:20+00:00",
+                    "downloads": {"server": {"url": "http://stub/server.jar", "sha1": "2aae6c35c94fcfb415dbe95f408b9ce91ee846ed", "size": 11}}
+                }"#,
+            )
+            .route("http://stub/server.jar", "hello world");
+        let dir = tempfile::tempdir().unwrap();
+
+        let vanilla = Vanilla {
+            manifest_url: "http://stub/manifest.json".to_string(),
+            client: HttpClient::with_backend(backend),
+        };
+        let jar = vanilla.install("1.20.4", dir.path()).unwrap();
+
+        assert_eq!(fs::read(jar).unwrap(), b"hello world");
+    }
+}
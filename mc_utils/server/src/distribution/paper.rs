@@ -0,0 +1,175 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use super::ServerDistribution;
+use crate::{download::download_file, http::HttpClient};
+
+pub const PAPER_API_URL: &str = "https://api.papermc.io/v2";
+pub const PURPUR_API_URL: &str = "https://api.purpurmc.org/v2";
+
+/// A server from the PaperMC build api
+///
+/// The latest build is used unless a specific build is set.
+#[derive(Debug, Clone)]
+pub struct Paper {
+    pub api_url: String,
+    /// The PaperMC project, e.g. `paper` or `folia`
+    pub project: String,
+    pub build: Option<u32>,
+    pub client: HttpClient,
+}
+
+impl Default for Paper {
+    fn default() -> Self {
+        Paper {
+            api_url: PAPER_API_URL.to_string(),
+            project: "paper".to_string(),
+            build: None,
+            client: HttpClient::default(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PaperBuilds {
+    builds: Vec<PaperBuild>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PaperBuild {
+    build: u32,
+    downloads: PaperDownloads,
+}
+
+#[derive(Debug, Deserialize)]
+struct PaperDownloads {
+    application: PaperDownload,
+}
+
+#[derive(Debug, Deserialize)]
+struct PaperDownload {
+    name: String,
+}
+
+impl ServerDistribution for Paper {
+    fn install(&self, version: &str, dir: &Path) -> io::Result<PathBuf> {
+        let version_url = format!(
+            "{}/projects/{}/versions/{}",
+            self.api_url, self.project, version
+        );
+        let mut builds: PaperBuilds = self.client.get_json(&format!("{}/builds", version_url))?;
+
+        // The builds are listed from oldest to newest
+        let build = match self.build {
+            Some(number) => builds
+                .builds
+                .into_iter()
+                .find(|build| build.build == number),
+            None => builds.builds.pop(),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Could not find the build"))?;
+
+        let name = build.downloads.application.name;
+        let url = format!("{}/builds/{}/downloads/{}", version_url, build.build, name);
+        let jar = dir.join(name);
+        download_file(&self.client, &url, &jar)?;
+        Ok(jar)
+    }
+}
+
+/// A server from the purpur build api
+#[derive(Debug, Clone)]
+pub struct Purpur {
+    pub api_url: String,
+    pub build: Option<String>,
+    pub client: HttpClient,
+}
+
+impl Default for Purpur {
+    fn default() -> Self {
+        Purpur {
+            api_url: PURPUR_API_URL.to_string(),
+            build: None,
+            client: HttpClient::default(),
+        }
+    }
+}
+
+impl ServerDistribution for Purpur {
+    fn install(&self, version: &str, dir: &Path) -> io::Result<PathBuf> {
+        let build = self.build.as_deref().unwrap_or("latest");
+        let url = format!("{}/purpur/{}/{}/download", self.api_url, version, build);
+
+        let jar = dir.join(format!("purpur-{}-{}.jar", version, build));
+        download_file(&self.client, &url, &jar)?;
+        Ok(jar)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{Paper, Purpur};
+    use crate::{
+        distribution::ServerDistribution,
+        http::{HttpClient, StubBackend},
+        test_util::serve,
+    };
+
+    #[test]
+    fn test_install_paper() {
+        let url = serve(vec![
+            (
+                "/projects/paper/versions/1.20.4/builds",
+                br#"{"builds": [
+                    {"build": 1, "downloads": {"application": {"name": "paper-1.20.4-1.jar"}}},
+                    {"build": 2, "downloads": {"application": {"name": "paper-1.20.4-2.jar"}}}
+                ]}"#
+                .to_vec(),
+            ),
+            (
+                "/projects/paper/versions/1.20.4/builds/2/downloads/paper-1.20.4-2.jar",
+                b"paper".to_vec(),
+            ),
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+
+        let paper = Paper {
+            api_url: url,
+            ..Paper::default()
+        };
+        let jar = paper.install("1.20.4", dir.path()).unwrap();
+
+        assert_eq!(jar, dir.path().join("paper-1.20.4-2.jar"));
+        assert_eq!(fs::read(jar).unwrap(), b"paper");
+    }
+
+    #[test]
+    fn test_install_purpur() {
+        let backend = StubBackend::new()
+            .route("http://stub/purpur/1.20.4/latest/download", "latest")
+            .route("http://stub/purpur/1.20.4/2176/download", "2176");
+        let dir = tempfile::tempdir().unwrap();
+
+        let purpur = Purpur {
+            api_url: "http://stub".to_string(),
+            build: None,
+            client: HttpClient::with_backend(backend),
+        };
+        let jar = purpur.install("1.20.4", dir.path()).unwrap();
+        assert_eq!(jar, dir.path().join("purpur-1.20.4-latest.jar"));
+        assert_eq!(fs::read(jar).unwrap(), b"latest");
+
+        let purpur = Purpur {
+            build: Some("2176".to_string()),
+            ..purpur
+        };
+        let jar = purpur.install("1.20.4", dir.path()).unwrap();
+        assert_eq!(fs::read(jar).unwrap(), b"2176");
+    }
+}
@@ -0,0 +1,662 @@
+//! Downloading files, optionally verified against a known hash and size
+//!
+//! Downloads are retried with backoff after transient failures and resumed from the
+//! partially downloaded file using http range requests.
+use std::{
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use sha1::{Digest, Sha1};
+
+use crate::http::{HttpClient, HttpResponse};
+
+pub type Result<T> = std::result::Result<T, DownloadError>;
+
+#[derive(Debug)]
+pub enum DownloadError {
+    IoError(io::Error),
+    /// The server responded with an unexpected status
+    HttpStatus {
+        url: String,
+        status: u16,
+    },
+    /// The downloaded file does not have the published sha1 hash
+    HashMismatch {
+        expected: String,
+        actual: String,
+    },
+    /// The downloaded file does not have the published size
+    SizeMismatch {
+        expected: u64,
+        actual: u64,
+    },
+    /// The download was cancelled using a [`CancelToken`]
+    Cancelled,
+}
+
+impl DownloadError {
+    /// Whether retrying the download might succeed
+    fn is_transient(&self) -> bool {
+        match self {
+            DownloadError::IoError(_) => true,
+            DownloadError::HttpStatus { status, .. } => *status == 429 || *status >= 500,
+            _ => false,
+        }
+    }
+}
+
+impl From<io::Error> for DownloadError {
+    fn from(error: io::Error) -> Self {
+        DownloadError::IoError(error)
+    }
+}
+
+impl From<DownloadError> for io::Error {
+    fn from(error: DownloadError) -> Self {
+        match error {
+            DownloadError::IoError(error) => error,
+            DownloadError::HttpStatus { status: 404, .. } => {
+                io::Error::new(io::ErrorKind::NotFound, error)
+            }
+            DownloadError::Cancelled => io::Error::new(io::ErrorKind::Interrupted, error),
+            other => io::Error::new(io::ErrorKind::InvalidData, other),
+        }
+    }
+}
+
+impl Display for DownloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DownloadError::IoError(error) => error.fmt(f),
+            DownloadError::HttpStatus { url, status } => {
+                write!(f, "Request to {} failed with status {}", url, status)
+            }
+            DownloadError::HashMismatch { expected, actual } => write!(
+                f,
+                "Hash mismatch: expected sha1 {}, got {}",
+                expected, actual
+            ),
+            DownloadError::SizeMismatch { expected, actual } => write!(
+                f,
+                "Size mismatch: expected {} bytes, got {}",
+                expected, actual
+            ),
+            DownloadError::Cancelled => write!(f, "The download was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {}
+
+/// The published checksum of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    /// The lowercase hex encoded sha1 hash
+    pub sha1: String,
+    pub size: Option<u64>,
+}
+
+/// The state of a running download, passed to the progress callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub downloaded: u64,
+    /// The total size, if it is known from the checksum or the response
+    pub total: Option<u64>,
+}
+
+pub type ProgressCallback = Arc<dyn Fn(Progress) + Send + Sync>;
+
+/// Cancels all downloads which use a clone of this token
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Downloads files with progress reporting, retries and resumption
+///
+/// While downloading, the data is written to a temporary file next to the destination,
+/// which is only renamed once the download is complete and verified.
+/// After a transient failure or a cancellation the temporary file is kept,
+/// so the next download of the same file continues where the last one stopped.
+#[derive(Clone)]
+pub struct Downloader {
+    client: HttpClient,
+    retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    resume: bool,
+    progress: Option<ProgressCallback>,
+    cancel: CancelToken,
+}
+
+impl Downloader {
+    pub fn new(client: HttpClient) -> Self {
+        Downloader {
+            client,
+            retries: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            resume: true,
+            progress: None,
+            cancel: CancelToken::new(),
+        }
+    }
+
+    pub fn client(&self) -> &HttpClient {
+        &self.client
+    }
+
+    /// The number of retries after transient failures
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// The delay before the first retry, which is doubled after every retry up to 'max'
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Whether to continue partial downloads instead of starting over
+    ///
+    /// Only downloads with a checksum are continued, since it detects stale partial files.
+    pub fn resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    /// Calls 'callback' whenever data was received
+    pub fn on_progress(mut self, callback: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// Downloads 'url' to 'destination' and verifies it against 'checksum', if given
+    ///
+    /// On success, the total number of bytes is returned
+    pub fn download(
+        &self,
+        url: &str,
+        destination: impl AsRef<Path>,
+        checksum: Option<&Checksum>,
+    ) -> Result<u64> {
+        let destination = destination.as_ref();
+        let temp_path = temp_path(destination);
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 0;
+
+        loop {
+            let error = match self.try_download(url, &temp_path, checksum) {
+                Ok(size) => {
+                    fs::rename(&temp_path, destination)?;
+                    return Ok(size);
+                }
+                Err(error) => error,
+            };
+
+            if error.is_transient() && attempt < self.retries && !self.cancel.is_cancelled() {
+                attempt += 1;
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(self.max_backoff);
+                continue;
+            }
+
+            let keep_partial =
+                self.resume && (error.is_transient() || matches!(error, DownloadError::Cancelled));
+            if !keep_partial {
+                fs::remove_file(&temp_path).ok();
+            }
+            return Err(error);
+        }
+    }
+
+    fn try_download(
+        &self,
+        url: &str,
+        temp_path: &Path,
+        checksum: Option<&Checksum>,
+    ) -> Result<u64> {
+        if self.cancel.is_cancelled() {
+            return Err(DownloadError::Cancelled);
+        }
+
+        let expected_size = checksum.and_then(|checksum| checksum.size);
+        // Without a checksum a stale partial file from another version could not be detected
+        let mut offset = match fs::metadata(temp_path) {
+            Ok(metadata) if self.resume && checksum.is_some() => metadata.len(),
+            _ => 0,
+        };
+        if expected_size.is_some_and(|size| offset > size) {
+            offset = 0;
+        }
+
+        let mut hasher = Sha1::new();
+        let (mut out, mut body, total) = if offset > 0 && expected_size == Some(offset) {
+            // The previous attempt received everything but was not verified
+            io::copy(&mut File::open(temp_path)?, &mut hasher)?;
+            let out = OpenOptions::new().append(true).open(temp_path)?;
+            (
+                out,
+                Box::new(io::empty()) as Box<dyn Read + Send>,
+                Some(offset),
+            )
+        } else {
+            let mut response = self.request(url, offset)?;
+            if offset > 0
+                && response.status == 206
+                && content_range_start(&response) != Some(offset)
+            {
+                // The server sent a different part, so start over
+                offset = 0;
+                response = self.request(url, offset)?;
+            }
+            let content_length = response
+                .header("Content-Length")
+                .and_then(|length| length.parse::<u64>().ok());
+
+            match response.status {
+                206 if offset > 0 => {
+                    io::copy(&mut File::open(temp_path)?, &mut hasher)?;
+                    let out = OpenOptions::new().append(true).open(temp_path)?;
+                    let total = content_length.map(|length| length + offset);
+                    (out, response.body, expected_size.or(total))
+                }
+                200..=299 => {
+                    offset = 0;
+                    let out = File::create(temp_path)?;
+                    (out, response.body, expected_size.or(content_length))
+                }
+                status => {
+                    return Err(DownloadError::HttpStatus {
+                        url: url.to_string(),
+                        status,
+                    })
+                }
+            }
+        };
+
+        let size = self.transfer(&mut body, &mut out, &mut hasher, offset, total)?;
+
+        if let Some(checksum) = checksum {
+            verify(hasher, size, checksum)?;
+        }
+        Ok(size)
+    }
+
+    /// Requests 'url', starting at 'offset'
+    fn request(&self, url: &str, offset: u64) -> Result<HttpResponse> {
+        let range = format!("bytes={}-", offset);
+        let headers: &[(&str, &str)] = if offset > 0 {
+            &[("Range", &range)]
+        } else {
+            &[]
+        };
+        Ok(self.client.send(url, headers)?)
+    }
+
+    /// Copies 'reader' to 'out' and reports the progress, starting at 'offset'
+    fn transfer(
+        &self,
+        reader: &mut impl Read,
+        out: &mut File,
+        hasher: &mut Sha1,
+        offset: u64,
+        total: Option<u64>,
+    ) -> Result<u64> {
+        let mut buffer = [0; 64 * 1024];
+        let mut downloaded = offset;
+
+        loop {
+            if self.cancel.is_cancelled() {
+                out.flush()?;
+                return Err(DownloadError::Cancelled);
+            }
+
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            out.write_all(&buffer[..read])?;
+            downloaded += read as u64;
+
+            if let Some(progress) = &self.progress {
+                progress(Progress { downloaded, total });
+            }
+        }
+        out.flush()?;
+
+        Ok(downloaded)
+    }
+}
+
+impl Default for Downloader {
+    fn default() -> Self {
+        Downloader::new(HttpClient::default())
+    }
+}
+
+/// Downloads a file from 'url' to the file at 'destination'
+///
+/// On success, the total number of bytes is returned
+pub(crate) fn download_file<U: AsRef<Path>>(
+    client: &HttpClient,
+    url: &str,
+    destination: U,
+) -> io::Result<u64> {
+    Ok(Downloader::new(client.clone()).download(url, destination, None)?)
+}
+
+/// Downloads a file from 'url' to 'destination' and verifies it against 'checksum'
+///
+/// 'destination' never contains a partial or corrupt file, see [`Downloader`].
+/// On success, the total number of bytes is returned
+pub fn download_verified(
+    client: &HttpClient,
+    url: &str,
+    destination: impl AsRef<Path>,
+    checksum: &Checksum,
+) -> Result<u64> {
+    Downloader::new(client.clone()).download(url, destination, Some(checksum))
+}
+
+/// Computes the lowercase hex encoded sha1 hash of the file at 'path'
+pub fn sha1_file(path: impl AsRef<Path>) -> io::Result<String> {
+    let mut hasher = Sha1::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex(&hasher.finalize()))
+}
+
+/// Checks the hash and size of a completed download against 'checksum'
+fn verify(hasher: Sha1, size: u64, checksum: &Checksum) -> Result<()> {
+    if let Some(expected) = checksum.size {
+        if expected != size {
+            return Err(DownloadError::SizeMismatch {
+                expected,
+                actual: size,
+            });
+        }
+    }
+
+    let actual = hex(&hasher.finalize());
+    if !actual.eq_ignore_ascii_case(&checksum.sha1) {
+        return Err(DownloadError::HashMismatch {
+            expected: checksum.sha1.clone(),
+            actual,
+        });
+    }
+
+    Ok(())
+}
+
+/// The first byte of a `Content-Range: bytes <start>-<end>/<size>` header
+fn content_range_start(response: &HttpResponse) -> Option<u64> {
+    let range = response.header("Content-Range")?.strip_prefix("bytes ")?;
+    range.split('-').next()?.trim().parse().ok()
+}
+
+/// The temporary file used while downloading to 'destination'
+fn temp_path(destination: &Path) -> PathBuf {
+    let mut file_name = destination.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".part");
+    destination.with_file_name(file_name)
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs, io,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::{download_verified, CancelToken, Checksum, DownloadError, Downloader, Progress};
+    use crate::{
+        http::{HttpBackend, HttpClient, HttpRequest, HttpResponse, StubBackend},
+        test_util::serve,
+    };
+
+    /// The sha1 hash of `hello world`
+    const HELLO_SHA1: &str = "2aae6c35c94fcfb415dbe95f408b9ce91ee846ed";
+
+    #[test]
+    fn test_download_verified() {
+        let url = serve(vec![("/file", b"hello world".to_vec())]);
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("file");
+
+        let checksum = Checksum {
+            sha1: HELLO_SHA1.to_string(),
+            size: Some(11),
+        };
+        let size = download_verified(
+            &HttpClient::default(),
+            &format!("{}/file", url),
+            &destination,
+            &checksum,
+        )
+        .unwrap();
+
+        assert_eq!(size, 11);
+        assert_eq!(fs::read(&destination).unwrap(), b"hello world");
+        assert!(!dir.path().join("file.part").exists());
+    }
+
+    #[test]
+    fn test_download_hash_mismatch() {
+        let url = serve(vec![("/file", b"hello wrld".to_vec())]);
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("file");
+
+        let checksum = Checksum {
+            sha1: HELLO_SHA1.to_string(),
+            size: None,
+        };
+        let result = download_verified(
+            &HttpClient::default(),
+            &format!("{}/file", url),
+            &destination,
+            &checksum,
+        );
+
+        assert!(matches!(result, Err(DownloadError::HashMismatch { .. })));
+        assert!(!destination.exists());
+        assert!(!dir.path().join("file.part").exists());
+    }
+
+    #[test]
+    fn test_download_size_mismatch() {
+        let url = serve(vec![("/file", b"hello".to_vec())]);
+        let dir = tempfile::tempdir().unwrap();
+
+        let checksum = Checksum {
+            sha1: HELLO_SHA1.to_string(),
+            size: Some(11),
+        };
+        let result = download_verified(
+            &HttpClient::default(),
+            &format!("{}/file", url),
+            dir.path().join("file"),
+            &checksum,
+        );
+
+        assert!(matches!(
+            result,
+            Err(DownloadError::SizeMismatch {
+                expected: 11,
+                actual: 5
+            })
+        ));
+    }
+
+    #[test]
+    fn test_resume_download() {
+        let backend = Arc::new(StubBackend::new().route("http://stub/file", "hello world"));
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("file");
+        fs::write(dir.path().join("file.part"), "hello ").unwrap();
+
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let reported = progress.clone();
+        let downloader = Downloader::new(HttpClient::from_arc(backend.clone()))
+            .on_progress(move |progress| reported.lock().unwrap().push(progress));
+        let checksum = Checksum {
+            sha1: HELLO_SHA1.to_string(),
+            size: Some(11),
+        };
+        downloader
+            .download("http://stub/file", &destination, Some(&checksum))
+            .unwrap();
+
+        assert_eq!(fs::read(&destination).unwrap(), b"hello world");
+        assert_eq!(
+            backend.requests()[0].headers,
+            vec![("Range".to_string(), "bytes=6-".to_string())]
+        );
+        assert_eq!(
+            progress.lock().unwrap().last(),
+            Some(&Progress {
+                downloaded: 11,
+                total: Some(11)
+            })
+        );
+    }
+
+    #[test]
+    fn test_no_resume_without_checksum() {
+        let backend = Arc::new(StubBackend::new().route("http://stub/file", "hello world"));
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("file");
+        // A stale partial file of another version
+        fs::write(dir.path().join("file.part"), "howdy ").unwrap();
+
+        Downloader::new(HttpClient::from_arc(backend.clone()))
+            .download("http://stub/file", &destination, None)
+            .unwrap();
+
+        assert_eq!(fs::read(&destination).unwrap(), b"hello world");
+        assert!(backend.requests()[0].headers.is_empty());
+    }
+
+    /// Answers range requests with the whole body
+    #[derive(Debug)]
+    struct IgnoredRangeBackend;
+
+    impl HttpBackend for IgnoredRangeBackend {
+        fn send(&self, _request: &HttpRequest) -> io::Result<HttpResponse> {
+            Ok(HttpResponse {
+                status: 206,
+                headers: vec![("Content-Range".to_string(), "bytes 0-10/11".to_string())],
+                body: Box::new(io::Cursor::new(b"hello world".to_vec())),
+            })
+        }
+    }
+
+    #[test]
+    fn test_resume_wrong_content_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("file");
+        fs::write(dir.path().join("file.part"), "hello ").unwrap();
+
+        let checksum = Checksum {
+            sha1: HELLO_SHA1.to_string(),
+            size: Some(11),
+        };
+        Downloader::new(HttpClient::with_backend(IgnoredRangeBackend))
+            .download("http://stub/file", &destination, Some(&checksum))
+            .unwrap();
+
+        assert_eq!(fs::read(&destination).unwrap(), b"hello world");
+    }
+
+    /// Fails the first request with a transport error
+    #[derive(Debug)]
+    struct FlakyBackend {
+        failed: Mutex<bool>,
+        inner: StubBackend,
+    }
+
+    impl HttpBackend for FlakyBackend {
+        fn send(&self, request: &HttpRequest) -> io::Result<HttpResponse> {
+            let mut failed = self.failed.lock().unwrap();
+            if !*failed {
+                *failed = true;
+                return Err(io::Error::new(io::ErrorKind::ConnectionReset, "reset"));
+            }
+            self.inner.send(request)
+        }
+    }
+
+    #[test]
+    fn test_retry_download() {
+        let backend = FlakyBackend {
+            failed: Mutex::new(false),
+            inner: StubBackend::new().route("http://stub/file", "hello world"),
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("file");
+
+        let downloader = Downloader::new(HttpClient::with_backend(backend))
+            .backoff(Duration::ZERO, Duration::ZERO);
+        assert_eq!(
+            downloader
+                .download("http://stub/file", &destination, None)
+                .unwrap(),
+            11
+        );
+
+        let result = Downloader::new(HttpClient::with_backend(StubBackend::new()))
+            .backoff(Duration::ZERO, Duration::ZERO)
+            .download("http://stub/missing", &destination, None);
+        assert!(matches!(
+            result,
+            Err(DownloadError::HttpStatus { status: 404, .. })
+        ));
+    }
+
+    #[test]
+    fn test_cancel_download() {
+        let backend = StubBackend::new().route("http://stub/file", "hello world");
+        let dir = tempfile::tempdir().unwrap();
+
+        let token = CancelToken::new();
+        token.cancel();
+        let result = Downloader::new(HttpClient::with_backend(backend))
+            .cancel_token(token)
+            .download("http://stub/file", dir.path().join("file"), None);
+
+        assert!(matches!(result, Err(DownloadError::Cancelled)));
+    }
+}
@@ -0,0 +1,371 @@
+//! Running GameTests headlessly and reporting their results
+use std::{fmt::Write as _, fs::File, io::Write, path::Path, process::Child, time::Duration};
+
+use crate::instance::{run_server, InstanceError, Result, ServerBuilder, ServerLog};
+
+/// The main class which starts the server in GameTest mode
+pub const GAMETEST_MAIN_CLASS: &str = "net.minecraft.gametest.framework.GameTestMain";
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// How the tests are run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameTestMode {
+    /// Starts the server with the given GameTest main class, which runs all tests and exits
+    ///
+    /// The world template and datapacks are installed into the world of the builder,
+    /// so its name has to match the world loaded by the main class.
+    GameTestServer { main_class: String },
+    /// Starts a regular server and executes `/test runall`
+    RunAll,
+}
+
+impl Default for GameTestMode {
+    fn default() -> Self {
+        GameTestMode::GameTestServer {
+            main_class: GAMETEST_MAIN_CLASS.to_string(),
+        }
+    }
+}
+
+/// The result of a single GameTest
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResult {
+    pub name: String,
+    pub passed: bool,
+    /// Optional tests do not cause the run to fail
+    pub required: bool,
+    /// The failure message, if the test failed
+    pub message: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+/// The results of a GameTest run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TestReport {
+    pub results: Vec<TestResult>,
+}
+
+impl TestReport {
+    /// Collects the test results from the server log lines
+    pub fn from_log<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let results = lines.into_iter().filter_map(parse_test_result).collect();
+        TestReport { results }
+    }
+
+    /// The required tests which failed
+    pub fn failures(&self) -> impl Iterator<Item = &TestResult> {
+        self.results
+            .iter()
+            .filter(|result| result.required && !result.passed)
+    }
+
+    /// Returns true if no required test failed
+    pub fn success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// The exit status for a CI job, 1 if any required test failed and 0 otherwise
+    pub fn exit_code(&self) -> i32 {
+        if self.success() {
+            0
+        } else {
+            1
+        }
+    }
+
+    /// Formats the results as a JUnit XML report
+    ///
+    /// Failed optional tests are reported as skipped.
+    pub fn to_junit_xml(&self) -> String {
+        let skipped = self
+            .results
+            .iter()
+            .filter(|result| !result.required && !result.passed)
+            .count();
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        writeln!(
+            xml,
+            "<testsuite name=\"gametest\" tests=\"{}\" failures=\"{}\" skipped=\"{}\">",
+            self.results.len(),
+            self.failures().count(),
+            skipped
+        )
+        .unwrap();
+
+        for result in &self.results {
+            let time = result.duration.unwrap_or_default().as_secs_f64();
+            write!(
+                xml,
+                "  <testcase name=\"{}\" classname=\"gametest\" time=\"{:.3}\"",
+                escape_xml(&result.name),
+                time
+            )
+            .unwrap();
+
+            if result.passed {
+                xml.push_str("/>\n");
+                continue;
+            }
+
+            let tag = if result.required {
+                "failure"
+            } else {
+                "skipped"
+            };
+            let message = escape_xml(result.message.as_deref().unwrap_or_default());
+            writeln!(
+                xml,
+                ">\n    <{} message=\"{}\"/>\n  </testcase>",
+                tag, message
+            )
+            .unwrap();
+        }
+
+        xml.push_str("</testsuite>\n");
+        xml
+    }
+
+    /// Writes the JUnit XML report to 'path'
+    pub fn write_junit(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        File::create(path)?.write_all(self.to_junit_xml().as_bytes())
+    }
+}
+
+/// Runs the GameTests of a server
+#[derive(Debug, Clone)]
+pub struct GameTestRunner {
+    builder: ServerBuilder,
+    mode: GameTestMode,
+    timeout: Duration,
+}
+
+impl GameTestRunner {
+    pub fn new(builder: ServerBuilder) -> Self {
+        GameTestRunner {
+            builder,
+            mode: GameTestMode::default(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    pub fn mode(mut self, mode: GameTestMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets how long the tests may run in [`GameTestMode::RunAll`]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Runs all tests and collects their results
+    pub fn run(self) -> Result<TestReport> {
+        match &self.mode {
+            GameTestMode::GameTestServer { main_class } => self.run_gametest_server(main_class),
+            GameTestMode::RunAll => self.run_all(),
+        }
+    }
+
+    fn run_gametest_server(&self, main_class: &str) -> Result<TestReport> {
+        let mut builder = self.builder.clone();
+        builder.prepare()?;
+
+        let main_class_arg = format!("-DbundlerMainClass={}", main_class);
+        let process = run_server(builder.jar(), &[], &[&main_class_arg])?;
+        collect_report(process)
+    }
+
+    fn run_all(self) -> Result<TestReport> {
+        let mut instance = self.builder.build()?;
+        let log_start = instance.log().len();
+        instance.command("test runall")?;
+
+        instance
+            .log()
+            .wait_for(log_start, Some(self.timeout), is_summary_line)
+            .ok_or_else(|| InstanceError::Other("The tests did not finish in time".to_string()))?;
+
+        let lines = instance.log().lines_since(log_start);
+        instance.try_stop()?;
+        Ok(TestReport::from_log(lines.iter().map(String::as_str)))
+    }
+}
+
+/// Waits for the GameTest server 'process' to exit and parses the results from its output
+///
+/// Returns Err if the server exited before printing a summary, or if it failed although all tests passed.
+fn collect_report(mut process: Child) -> Result<TestReport> {
+    process.stdin.take();
+    let stdout = process
+        .stdout
+        .take()
+        .ok_or_else(|| InstanceError::Other("Can not access stdout".to_string()))?;
+
+    // The GameTest server exits on its own once all tests are done
+    let log = ServerLog::spawn_reader(stdout);
+    let status = process.wait()?;
+    log.wait_for(0, None, |_| false);
+
+    let lines = log.lines_since(0);
+    if !lines.iter().any(|line| is_summary_line(line)) {
+        return Err(InstanceError::Other(format!(
+            "The GameTest server exited without reporting results: {}",
+            status
+        )));
+    }
+
+    let report = TestReport::from_log(lines.iter().map(String::as_str));
+    if !status.success() && report.success() {
+        return Err(InstanceError::Other(format!(
+            "The GameTest server did not exit successfully: {}",
+            status
+        )));
+    }
+    Ok(report)
+}
+
+/// Returns true for the line printed once all tests are done
+fn is_summary_line(line: &str) -> bool {
+    line.contains("required tests passed") || line.contains("required tests failed")
+}
+
+/// Parses a line like `[Server thread/INFO]: test.example passed! (52ms)`
+/// or `[Server thread/ERROR]: test.example failed! Expected block`
+fn parse_test_result(line: &str) -> Option<TestResult> {
+    let message = line.split_once("]: ").map_or(line, |(_, message)| message);
+
+    if let Some((name, rest)) = message.split_once(" passed! ") {
+        let duration = rest
+            .trim()
+            .strip_prefix('(')
+            .and_then(|rest| rest.strip_suffix("ms)"))
+            .and_then(|millis| millis.parse().ok())
+            .map(Duration::from_millis);
+        return Some(TestResult {
+            name: name.trim().to_string(),
+            passed: true,
+            required: true,
+            message: None,
+            duration,
+        });
+    }
+
+    if let Some(rest) = message.strip_prefix("(optional) ") {
+        let (name, failure) = rest.split_once(" failed. ")?;
+        return Some(failed_test(name, failure, false));
+    }
+
+    let (name, failure) = message.split_once(" failed! ")?;
+    Some(failed_test(name, failure, true))
+}
+
+fn failed_test(name: &str, message: &str, required: bool) -> TestResult {
+    TestResult {
+        name: name.trim().to_string(),
+        passed: false,
+        required,
+        message: Some(message.trim().to_string()),
+        duration: None,
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        process::{Command, Stdio},
+        time::Duration,
+    };
+
+    use super::{collect_report, TestReport};
+    use crate::instance::InstanceError;
+
+    /// Runs a shell script which stands in for a GameTest server
+    fn stand_in_server(script: &str) -> std::process::Child {
+        Command::new("sh")
+            .args(["-c", script])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap()
+    }
+
+    const LOG: &[&str] = &[
+        "[12:00:00] [Server thread/INFO]: Running test batch 'defaultBatch:0' (3 tests)...",
+        "[12:00:01] [Server thread/INFO]: example.passing passed! (52ms)",
+        "[12:00:01] [Server thread/ERROR]: example.failing failed! Expected <stone> at 1,2,3",
+        "[12:00:01] [Server thread/WARN]: (optional) example.flaky failed. Timed out",
+        "[12:00:02] [Server thread/INFO]: 1 required tests failed :(",
+    ];
+
+    #[test]
+    fn test_parse_log() {
+        let report = TestReport::from_log(LOG.iter().copied());
+
+        assert_eq!(report.results.len(), 3);
+        assert!(report.results[0].passed);
+        assert_eq!(report.results[0].duration, Some(Duration::from_millis(52)));
+        assert_eq!(
+            report.results[1].message.as_deref(),
+            Some("Expected <stone> at 1,2,3")
+        );
+        assert!(!report.results[2].required);
+        assert_eq!(report.failures().count(), 1);
+        assert_eq!(report.exit_code(), 1);
+    }
+
+    #[test]
+    fn test_junit_xml() {
+        let xml = TestReport::from_log(LOG.iter().copied()).to_junit_xml();
+
+        assert!(
+            xml.contains("<testsuite name=\"gametest\" tests=\"3\" failures=\"1\" skipped=\"1\">")
+        );
+        assert!(xml.contains(
+            "<testcase name=\"example.passing\" classname=\"gametest\" time=\"0.052\"/>"
+        ));
+        assert!(xml.contains("<failure message=\"Expected &lt;stone&gt; at 1,2,3\"/>"));
+        assert!(xml.contains("<skipped message=\"Timed out\"/>"));
+    }
+
+    #[test]
+    fn test_collect_report() {
+        let server = stand_in_server(
+            "echo 'example.passing passed! (52ms)'; echo 'All 1 required tests passed :)'",
+        );
+        let report = collect_report(server).unwrap();
+        assert_eq!(report.results.len(), 1);
+        assert!(report.success());
+
+        // Failed tests are reported, whatever the exit code
+        let server = stand_in_server(
+            "echo 'example.failing failed! Expected stone'; echo '1 required tests failed :('; exit 1",
+        );
+        assert!(!collect_report(server).unwrap().success());
+    }
+
+    #[test]
+    fn test_collect_report_of_failed_server() {
+        let server = stand_in_server("echo 'Starting'; exit 1");
+        assert!(matches!(
+            collect_report(server),
+            Err(InstanceError::Other(_))
+        ));
+
+        let server = stand_in_server("echo 'All 0 required tests passed :)'; exit 3");
+        assert!(collect_report(server).is_err());
+
+        let server = stand_in_server("echo 'Starting'");
+        assert!(collect_report(server).is_err());
+    }
+}
@@ -0,0 +1,306 @@
+//! The http client used for all network access
+//!
+//! Requests are sent by an exchangeable [`HttpBackend`]. The default backend uses ureq,
+//! the [`StubBackend`] serves fixed responses from memory for offline tests.
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    io::{self, Cursor, Read},
+    sync::{Arc, Mutex},
+};
+
+use serde::de::DeserializeOwned;
+
+/// A GET request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpRequest {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+}
+
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Box<dyn Read + Send>,
+}
+
+impl HttpResponse {
+    /// Returns the value of the first header called 'name', ignoring case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl Debug for HttpResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpResponse")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Sends http requests
+///
+/// Responses with an error status must be returned as `Ok`, only transport failures are errors.
+pub trait HttpBackend: Debug + Send + Sync {
+    fn send(&self, request: &HttpRequest) -> io::Result<HttpResponse>;
+}
+
+/// The default backend, a custom agent can be used to configure proxies or timeouts
+#[derive(Debug, Clone)]
+pub struct UreqBackend {
+    agent: ureq::Agent,
+}
+
+impl UreqBackend {
+    pub fn new(agent: ureq::Agent) -> Self {
+        UreqBackend { agent }
+    }
+}
+
+impl Default for UreqBackend {
+    fn default() -> Self {
+        UreqBackend::new(ureq::Agent::new())
+    }
+}
+
+impl HttpBackend for UreqBackend {
+    fn send(&self, request: &HttpRequest) -> io::Result<HttpResponse> {
+        let mut ureq_request = self.agent.get(&request.url);
+        for (name, value) in &request.headers {
+            ureq_request = ureq_request.set(name, value);
+        }
+
+        let response = match ureq_request.call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(_, response)) => response,
+            Err(error) => return Err(io::Error::other(error)),
+        };
+
+        let headers = response
+            .headers_names()
+            .into_iter()
+            .filter_map(|name| {
+                let value = response.header(&name)?.to_string();
+                Some((name, value))
+            })
+            .collect();
+        Ok(HttpResponse {
+            status: response.status(),
+            headers,
+            body: Box::new(response.into_reader()),
+        })
+    }
+}
+
+/// Serves fixed responses from memory and answers unknown urls with 404
+///
+/// Range requests of the form `bytes=<start>-` are supported.
+/// All received requests are recorded, so tests can check the sent headers.
+#[derive(Debug, Default)]
+pub struct StubBackend {
+    routes: HashMap<String, Vec<u8>>,
+    requests: Mutex<Vec<HttpRequest>>,
+}
+
+impl StubBackend {
+    pub fn new() -> Self {
+        StubBackend::default()
+    }
+
+    /// Serves 'body' for requests to 'url'
+    pub fn route(mut self, url: impl Into<String>, body: impl Into<Vec<u8>>) -> Self {
+        self.routes.insert(url.into(), body.into());
+        self
+    }
+
+    /// Returns all requests received so far
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl HttpBackend for StubBackend {
+    fn send(&self, request: &HttpRequest) -> io::Result<HttpResponse> {
+        self.requests.lock().unwrap().push(request.clone());
+
+        let range_start = request
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("Range"))
+            .and_then(|(_, range)| {
+                range
+                    .strip_prefix("bytes=")?
+                    .strip_suffix('-')?
+                    .parse()
+                    .ok()
+            });
+
+        let mut headers = Vec::new();
+        let (status, body) = match (self.routes.get(&request.url), range_start) {
+            (Some(body), Some(start)) if start <= body.len() => {
+                headers.push((
+                    "Content-Range".to_string(),
+                    format!("bytes {}-{}/{}", start, body.len().max(1) - 1, body.len()),
+                ));
+                (206, body[start..].to_vec())
+            }
+            (Some(_), Some(_)) => (416, Vec::new()),
+            (Some(body), None) => (200, body.clone()),
+            (None, _) => (404, Vec::new()),
+        };
+        headers.push(("Content-Length".to_string(), body.len().to_string()));
+        Ok(HttpResponse {
+            status,
+            headers,
+            body: Box::new(Cursor::new(body)),
+        })
+    }
+}
+
+/// Sends requests through a [`HttpBackend`] and adds the configured headers
+///
+/// Mirrors replace the beginning of request urls, e.g. to redirect all requests
+/// for `https://piston-data.mojang.com` to a local mirror.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    backend: Arc<dyn HttpBackend>,
+    headers: Vec<(String, String)>,
+    mirrors: Vec<(String, String)>,
+}
+
+impl HttpClient {
+    pub fn new() -> Self {
+        HttpClient::with_backend(UreqBackend::default())
+    }
+
+    pub fn from_agent(agent: ureq::Agent) -> Self {
+        HttpClient::with_backend(UreqBackend::new(agent))
+    }
+
+    pub fn with_backend(backend: impl HttpBackend + 'static) -> Self {
+        HttpClient::from_arc(Arc::new(backend))
+    }
+
+    /// Uses a shared backend, e.g. to inspect a [`StubBackend`] after the requests
+    pub fn from_arc(backend: Arc<dyn HttpBackend>) -> Self {
+        HttpClient {
+            backend,
+            headers: Vec::new(),
+            mirrors: Vec::new(),
+        }
+    }
+
+    /// Sends the header with every request
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn user_agent(self, user_agent: impl Into<String>) -> Self {
+        self.header("User-Agent", user_agent)
+    }
+
+    /// Sends requests for urls starting with 'prefix' to 'replacement' instead
+    pub fn mirror(mut self, prefix: impl Into<String>, replacement: impl Into<String>) -> Self {
+        self.mirrors.push((prefix.into(), replacement.into()));
+        self
+    }
+
+    /// Sends a request with the configured and the additional 'headers'
+    ///
+    /// Unlike [`HttpClient::get`], this does not treat error statuses as errors.
+    pub fn send(&self, url: &str, headers: &[(&str, &str)]) -> io::Result<HttpResponse> {
+        let url = self
+            .mirrors
+            .iter()
+            .find_map(|(prefix, replacement)| {
+                url.strip_prefix(prefix.as_str())
+                    .map(|rest| format!("{}{}", replacement, rest))
+            })
+            .unwrap_or_else(|| url.to_string());
+
+        let mut request = HttpRequest {
+            url,
+            headers: self.headers.clone(),
+        };
+        request.headers.extend(
+            headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string())),
+        );
+        self.backend.send(&request)
+    }
+
+    /// Requests 'url' and returns a reader over the response body
+    ///
+    /// Responses with an error status are returned as error.
+    pub fn get(&self, url: &str) -> io::Result<Box<dyn Read + Send>> {
+        let response = self.send(url, &[])?;
+        match response.status {
+            200..=299 => Ok(response.body),
+            404 => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{}: 404 Not Found", url),
+            )),
+            status => Err(io::Error::other(format!("{}: status {}", url, status))),
+        }
+    }
+
+    /// Requests 'url' and returns the complete response body
+    pub fn get_bytes(&self, url: &str) -> io::Result<Vec<u8>> {
+        let mut content = Vec::new();
+        self.get(url)?.read_to_end(&mut content)?;
+        Ok(content)
+    }
+
+    /// Requests 'url' and deserializes the json response
+    pub fn get_json<T: DeserializeOwned>(&self, url: &str) -> io::Result<T> {
+        Ok(serde_json::from_reader(self.get(url)?)?)
+    }
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        HttpClient::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io, sync::Arc};
+
+    use super::{HttpClient, StubBackend};
+
+    #[test]
+    fn test_stub_backend() {
+        let backend = Arc::new(StubBackend::new().route("http://mirror/file", "content"));
+        let client = HttpClient::from_arc(backend.clone())
+            .user_agent("mc_utils")
+            .mirror("https://example.com", "http://mirror");
+
+        assert_eq!(
+            client.get_bytes("https://example.com/file").unwrap(),
+            b"content"
+        );
+        assert_eq!(
+            client
+                .get("https://example.com/other")
+                .err()
+                .unwrap()
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+
+        let requests = backend.requests();
+        assert_eq!(requests[0].url, "http://mirror/file");
+        assert_eq!(
+            requests[0].headers,
+            vec![("User-Agent".to_string(), "mc_utils".to_string())]
+        );
+    }
+}
@@ -7,6 +7,24 @@ pub enum InstanceError {
     Other(String),
     IoError(std::io::Error),
     DeserializationError(java_properties::PropertiesError),
+    JsonError(serde_json::Error),
+    RconError(rcon::Error),
+    /// A datapack was installed, but the server did not enable it
+    DatapackNotEnabled {
+        name: String,
+        /// The errors and warnings logged while reloading
+        errors: Vec<String>,
+    },
+    /// A backup was created, but saving could not be turned on again afterwards
+    SavingNotResumed {
+        backup: std::path::PathBuf,
+        error: Box<InstanceError>,
+    },
+    /// The server jar needs a newer java than the one installed
+    IncompatibleJava {
+        required: u32,
+        found: u32,
+    },
 }
 
 impl From<std::io::Error> for InstanceError {
@@ -21,12 +39,44 @@ impl From<java_properties::PropertiesError> for InstanceError {
     }
 }
 
+impl From<serde_json::Error> for InstanceError {
+    fn from(error: serde_json::Error) -> Self {
+        InstanceError::JsonError(error)
+    }
+}
+
+impl From<rcon::Error> for InstanceError {
+    fn from(error: rcon::Error) -> Self {
+        InstanceError::RconError(error)
+    }
+}
+
 impl Display for InstanceError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             InstanceError::Other(msg) => f.write_str(msg),
             InstanceError::IoError(error) => error.fmt(f),
             InstanceError::DeserializationError(error) => error.fmt(f),
+            InstanceError::JsonError(error) => error.fmt(f),
+            InstanceError::RconError(error) => error.fmt(f),
+            InstanceError::DatapackNotEnabled { name, errors } => {
+                write!(f, "Datapack {} was not enabled", name)?;
+                for error in errors {
+                    write!(f, "\n{}", error)?;
+                }
+                Ok(())
+            }
+            InstanceError::SavingNotResumed { backup, error } => write!(
+                f,
+                "Created backup {}, but could not turn saving on again: {}",
+                backup.display(),
+                error
+            ),
+            InstanceError::IncompatibleJava { required, found } => write!(
+                f,
+                "The server requires java {}, but java {} is installed",
+                required, found
+            ),
         }
     }
 }
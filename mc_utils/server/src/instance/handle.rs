@@ -1,4 +1,5 @@
 use std::{
+    ffi::OsString,
     path::Path,
     process::{Child, Command, Stdio},
 };
@@ -22,6 +23,29 @@ pub fn has_java() -> bool {
     }
 }
 
+/// Returns the major version of the installed java, e.g. 8 or 17
+pub fn java_version() -> Option<u32> {
+    // `java -version` prints to stderr
+    let output = Command::new("java").arg("-version").output().ok()?;
+    parse_java_version(&String::from_utf8_lossy(&output.stderr))
+}
+
+/// Parses the major version from the output of `java -version`
+///
+/// Versions before java 9 are reported as `1.8.0_292`, later ones as `17.0.2`.
+fn parse_java_version(output: &str) -> Option<u32> {
+    let version = output.split('"').nth(1)?;
+    let mut parts = version.split(|c: char| !c.is_ascii_digit());
+    match parts.next()?.parse().ok()? {
+        1 => parts.next()?.parse().ok(),
+        major => Some(major),
+    }
+}
+
+/// Starts the server 'path' with java
+///
+/// 'path' is usually the server jar. Forge 1.17 to 1.20.2 is instead started with the
+/// `unix_args.txt` args file in its `libraries` directory.
 pub fn run_server(
     path: impl AsRef<Path>,
     args: &[&str],
@@ -39,17 +63,79 @@ pub fn run_server(
     })?;
 
     let path = path.as_ref();
-    let dir = path
-        .parent()
-        .expect("Could not get parent dir of this path");
-
-    Command::new("java")
-        .args(java_args)
-        .arg("-jar")
-        .arg(path)
+    let mut command = Command::new("java");
+    command.args(java_args);
+    if is_args_file(path) {
+        let mut args_file = OsString::from("@");
+        args_file.push(path);
+        command.arg(args_file);
+    } else {
+        command.arg("-jar").arg(path);
+    }
+
+    command
         .args(args)
-        .current_dir(dir)
+        .current_dir(server_dir(path))
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .spawn()
 }
+
+/// Returns the directory the server at 'path' runs in
+///
+/// This is the directory of the jar, or the parent of the `libraries` directory for args files.
+pub(crate) fn server_dir(path: &Path) -> &Path {
+    let libraries_dir = path
+        .ancestors()
+        .find(|dir| dir.file_name().is_some_and(|name| name == "libraries"));
+    match libraries_dir.and_then(Path::parent) {
+        Some(dir) if is_args_file(path) => dir,
+        _ => path
+            .parent()
+            .expect("Could not get parent dir of this path"),
+    }
+}
+
+fn is_args_file(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "txt")
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{parse_java_version, server_dir};
+
+    #[test]
+    fn test_parse_java_version() {
+        assert_eq!(
+            parse_java_version(
+                "openjdk version \"17.0.2\" 2022-01-18\nOpenJDK Runtime Environment"
+            ),
+            Some(17)
+        );
+        assert_eq!(
+            parse_java_version("java version \"1.8.0_292\"\nJava(TM) SE Runtime Environment"),
+            Some(8)
+        );
+        assert_eq!(
+            parse_java_version("openjdk version \"21\" 2023-09-19"),
+            Some(21)
+        );
+        assert_eq!(parse_java_version("command not found"), None);
+    }
+
+    #[test]
+    fn test_server_dir() {
+        assert_eq!(
+            server_dir(Path::new("server/forge-1.20.4-shim.jar")),
+            Path::new("server")
+        );
+        assert_eq!(
+            server_dir(Path::new(
+                "server/libraries/net/minecraftforge/forge/1.20.1-47.2.0/unix_args.txt"
+            )),
+            Path::new("server")
+        );
+    }
+}
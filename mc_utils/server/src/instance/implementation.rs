@@ -2,19 +2,33 @@ use std::time::Duration;
 use std::{
     collections::HashMap,
     fs::OpenOptions,
-    io::BufRead,
     io::BufReader,
     io::{BufWriter, Write},
-    process::Child,
+    process::{Child, ExitStatus},
+};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
 };
-use std::{fs::File, path::PathBuf};
 
-use super::{run_server, InstanceError, Result};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use rcon::McRcon;
+
+use super::{
+    free_ports, handle::server_dir, java_version, run_server, InstanceError, Result, ServerLog,
+};
+use crate::{install_datapack_files, install_world_template, read_jar_version, JarVersion};
 
 pub const SERVER_PROPERTIES: &str = "server.properties";
 pub const EULA_TXT: &str = "eula.txt";
 pub const DEFAULT_WORLD_NAME: &str = "world";
 
+const SERVER_PORT: &str = "server-port";
+const ENABLE_RCON: &str = "enable-rcon";
+const RCON_PORT: &str = "rcon.port";
+const RCON_PASSWORD: &str = "rcon.password";
+const QUERY_PORT: &str = "query.port";
+
 /// A running minecraft server
 ///
 /// When this object is dropped, the running minecraft server gets killed.
@@ -26,6 +40,12 @@ pub struct ServerInstance {
     pub world_name: String,
     pub properties: HashMap<String, String>,
     process: Child,
+    log: ServerLog,
+    /// The builder used to restart the server
+    ///
+    /// Its `auto_ports` and `enable_rcon` options were already turned into properties by
+    /// [`ServerInstance::start`], so restarts reuse the same ports and rcon password.
+    builder: ServerBuilder,
 }
 
 impl ServerInstance {
@@ -35,13 +55,11 @@ impl ServerInstance {
     }
 
     /// Creates a new server instance builder with the server 'jar'
+    ///
+    /// The jar may also be a forge args file, see [`run_server`](crate::run_server).
     pub fn with_jar(jar: impl Into<PathBuf>) -> ServerBuilder {
         let jar: PathBuf = jar.into();
-        ServerBuilder::new(
-            jar.parent()
-                .expect("Could not find parent directory of jar"),
-        )
-        .server_path(jar)
+        ServerBuilder::new(server_dir(&jar)).server_path(jar)
     }
 
     /// Tries to stop the server gracefully
@@ -61,6 +79,84 @@ impl ServerInstance {
         &self.process
     }
 
+    /// Stops the server, replaces the world with the template and starts the server again
+    ///
+    /// Returns Err if no world template was configured or if the server could not be started again.
+    /// In the latter case the server stays stopped and calling this method again retries the restart.
+    pub fn reset_world(&mut self) -> Result<()> {
+        if self.builder.world_template.is_none() {
+            return Err(InstanceError::Other(
+                "No world template was configured".to_string(),
+            ));
+        }
+
+        // The server is already stopped if a previous restart failed
+        if self.try_wait()?.is_none() {
+            self.try_stop()?;
+        }
+
+        // Starting the server again installs the template
+        let instance = ServerInstance::start(self.builder.clone())?;
+        *self = instance;
+        Ok(())
+    }
+
+    /// The console output of the server
+    pub fn log(&self) -> &ServerLog {
+        &self.log
+    }
+
+    /// Returns the exit status of the server if it has exited, without blocking
+    pub fn try_wait(&mut self) -> Result<Option<ExitStatus>> {
+        Ok(self.process.try_wait()?)
+    }
+
+    /// The port on which the server accepts players
+    pub fn server_port(&self) -> Option<u16> {
+        self.port_property(SERVER_PORT)
+    }
+
+    /// The port of the rcon interface, which might be disabled
+    pub fn rcon_port(&self) -> Option<u16> {
+        self.port_property(RCON_PORT)
+    }
+
+    /// The port of the query interface, which might be disabled
+    pub fn query_port(&self) -> Option<u16> {
+        self.port_property(QUERY_PORT)
+    }
+
+    /// Returns whether the rcon interface of this server is enabled
+    pub fn rcon_enabled(&self) -> bool {
+        self.properties.get(ENABLE_RCON).map(String::as_str) == Some("true")
+    }
+
+    /// Connects a new rcon client to this server
+    ///
+    /// Returns Err if rcon is not enabled.
+    pub fn rcon(&self) -> Result<McRcon> {
+        if !self.rcon_enabled() {
+            return Err(InstanceError::Other(
+                "Rcon is not enabled on this server".to_string(),
+            ));
+        }
+
+        let port = self
+            .rcon_port()
+            .ok_or_else(|| InstanceError::Other("Invalid rcon port".to_string()))?;
+        let password = self
+            .properties
+            .get(RCON_PASSWORD)
+            .cloned()
+            .unwrap_or_default();
+
+        Ok(McRcon::new(("localhost", port), password)?)
+    }
+
+    fn port_property(&self, key: &str) -> Option<u16> {
+        self.properties.get(key)?.parse().ok()
+    }
+
     /// Executes a command at the server
     pub fn command(&mut self, command: &str) -> Result<()> {
         let mut stdin = self
@@ -85,46 +181,11 @@ impl ServerInstance {
     }
 
     /// Starts the server
-    fn start(builder: ServerBuilder) -> Result<Self> {
-        // initializes the properties file
-        let properties = {
-            let properties_path = builder.dir.join(SERVER_PROPERTIES);
-
-            // If the properties file does not exist yet, the server has to generate them
-            if !properties_path.exists() {
-                let mut proc = run_server(&builder.server_path, &["--initSettings"], &[])?;
-                let status_code = proc.wait()?;
-                if !status_code.success() {
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        format!("Server did not exit successfully: {}", status_code),
-                    )
-                    .into());
-                }
-            }
-
-            let f = OpenOptions::new()
-                .append(false)
-                .write(true)
-                .read(true)
-                .open(properties_path)?;
-            let mut properties: HashMap<String, String> =
-                java_properties::read(BufReader::new(f.try_clone()?))?;
-
-            // add the builder properties
-            if !builder.properties.is_empty() {
-                properties.extend(builder.properties);
-                java_properties::write(f, &properties)?;
-            }
-
-            properties
-        };
-
-        // Write the eula.txt file
-        {
-            let mut eula_file = File::create(builder.dir.join(EULA_TXT))?;
-            eula_file.write_all("eula=true".as_bytes())?;
-        }
+    ///
+    /// This clears `auto_ports` and `enable_rcon` of the stored builder after choosing the
+    /// ports and password, see [`ServerBuilder::prepare`].
+    fn start(mut builder: ServerBuilder) -> Result<Self> {
+        let properties = builder.prepare()?;
 
         // And finally start the server
         let mut process = run_server(
@@ -138,27 +199,25 @@ impl ServerInstance {
             .stdout
             .take()
             .ok_or_else(|| InstanceError::Other("Can not access stdout".to_string()))?;
-        let mut buffer = BufReader::new(stdout);
-
-        loop {
-            let mut line = String::new();
-            buffer.read_line(&mut line)?;
-
-            print!("[Server]: {}", line);
-            if line.contains("[Server thread/INFO]: Done ") || line.is_empty() {
-                break;
+        // The output has to be read continuously, otherwise the server blocks once the pipe is full.
+        // Only the startup is printed, the rest can be read from the log.
+        let mut loading = true;
+        let log = ServerLog::spawn_reader_with(stdout, move |line| {
+            if loading {
+                println!("[Server]: {}", line);
+                loading = !is_done_line(line);
             }
-        }
-
-        // For some reason the next line breaks the server when using mcrcon
-        // process.stdout.replace(buffer.into_inner());
+        });
+        log.wait_for(0, None, is_done_line);
 
         let instance = ServerInstance {
-            dir: builder.dir,
-            jar: builder.server_path,
-            world_name: builder.world_name,
+            dir: builder.dir.clone(),
+            jar: builder.server_path.clone(),
+            world_name: builder.world_name.clone(),
             properties,
             process,
+            log,
+            builder,
         };
 
         // wait a bit to be sure that rcon can be accessed
@@ -180,12 +239,16 @@ impl Drop for ServerInstance {
 }
 
 /// A minecraft server builder
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ServerBuilder {
     dir: PathBuf,
     server_path: PathBuf,
     world_name: String,
     properties: HashMap<String, String>,
+    auto_ports: bool,
+    enable_rcon: bool,
+    world_template: Option<PathBuf>,
+    datapacks: Vec<PathBuf>,
 }
 
 impl ServerBuilder {
@@ -202,13 +265,48 @@ impl ServerBuilder {
             server_path,
             world_name: DEFAULT_WORLD_NAME.to_string(),
             properties: HashMap::new(),
+            auto_ports: false,
+            enable_rcon: false,
+            world_template: None,
+            datapacks: Vec::new(),
+        }
+    }
+
+    /// The directory the server runs in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The path of the server jar
+    pub fn jar(&self) -> &Path {
+        &self.server_path
+    }
+
+    /// Reads the version embedded in the server jar
+    pub fn jar_version(&self) -> Result<JarVersion> {
+        Ok(read_jar_version(&self.server_path)?)
+    }
+
+    /// Checks that the installed java is new enough for the server jar
+    ///
+    /// Passes if either version is unknown, e.g. for jars older than 1.17.
+    pub fn check_java(&self) -> Result<()> {
+        let required = match self.jar_version().ok().and_then(|v| v.java_version) {
+            Some(required) => required,
+            None => return Ok(()),
+        };
+        match java_version() {
+            Some(found) if found < required => {
+                Err(InstanceError::IncompatibleJava { required, found })
+            }
+            _ => Ok(()),
         }
     }
 
     /// Sets the path of the server jar
     pub fn server_path(mut self, path: impl Into<PathBuf>) -> Self {
         let path = path.into();
-        if !path.starts_with(&self.server_path) {
+        if !path.starts_with(&self.dir) {
             panic!("The server path must be withing the server directory")
         }
         self.server_path = path;
@@ -221,9 +319,177 @@ impl ServerBuilder {
         self
     }
 
+    /// Sets a world directory or world archive which is copied into place on every start
+    ///
+    /// This replaces any existing world, so every start begins with the same world.
+    /// See also [`ServerInstance::reset_world`].
+    pub fn world_template(mut self, template: impl Into<PathBuf>) -> Self {
+        self.world_template = Some(template.into());
+        self
+    }
+
+    /// Adds a datapack folder or zip file, which is copied into the datapacks of the world on start
+    pub fn datapack(mut self, path: impl Into<PathBuf>) -> Self {
+        self.datapacks.push(path.into());
+        self
+    }
+
+    /// Picks free ports for the server, rcon and query interface when the server is started
+    ///
+    /// This allows running multiple servers at the same time.
+    /// Ports which were set explicitly with [`ServerBuilder::property`] are kept.
+    /// The chosen ports can be retrieved from the running [`ServerInstance`].
+    pub fn auto_ports(mut self) -> Self {
+        self.auto_ports = true;
+        self
+    }
+
+    /// Enables rcon with a random password on a free port
+    ///
+    /// A port or password which was set explicitly with [`ServerBuilder::property`] is kept.
+    /// A connected client can be obtained with [`ServerInstance::rcon`].
+    pub fn enable_rcon(mut self) -> Self {
+        self.enable_rcon = true;
+        self
+    }
+
     /// Sets a property for 'server.properties'
     pub fn property<T: Into<String>, U: Into<String>>(mut self, key: T, value: U) -> Self {
         self.properties.insert(key.into(), value.into());
         self
     }
+
+    /// Sets up the server directory like [`ServerBuilder::build`] does, without starting the server
+    ///
+    /// Writes the properties and the eula and installs the world template and the datapacks.
+    /// Returns the resulting server properties.
+    pub(crate) fn prepare(&mut self) -> Result<HashMap<String, String>> {
+        self.check_java()?;
+
+        self.resolve_properties()?;
+
+        // initializes the properties file
+        let properties = {
+            let properties_path = self.dir.join(SERVER_PROPERTIES);
+
+            // If the properties file does not exist yet, the server has to generate them
+            if !properties_path.exists() {
+                let mut proc = run_server(&self.server_path, &["--initSettings"], &[])?;
+                let status_code = proc.wait()?;
+                if !status_code.success() {
+                    return Err(std::io::Error::other(format!(
+                        "Server did not exit successfully: {}",
+                        status_code
+                    ))
+                    .into());
+                }
+            }
+
+            let f = OpenOptions::new()
+                .append(false)
+                .write(true)
+                .read(true)
+                .open(properties_path)?;
+            let mut properties: HashMap<String, String> =
+                java_properties::read(BufReader::new(f.try_clone()?))?;
+
+            // add the builder properties
+            if !self.properties.is_empty() {
+                properties.extend(self.properties.clone());
+                java_properties::write(f, &properties)?;
+            }
+
+            properties
+        };
+
+        // Write the eula.txt file
+        {
+            let mut eula_file = File::create(self.dir.join(EULA_TXT))?;
+            eula_file.write_all("eula=true".as_bytes())?;
+        }
+
+        if let Some(template) = &self.world_template {
+            install_world_template(template, self.dir.join(&self.world_name))?;
+        }
+
+        for datapack in &self.datapacks {
+            install_datapack_files(datapack, self.dir.join(&self.world_name))?;
+        }
+
+        Ok(properties)
+    }
+
+    /// Turns `auto_ports` and `enable_rcon` into properties, so restarts reuse the ports and password
+    pub(crate) fn resolve_properties(&mut self) -> Result<()> {
+        if self.auto_ports {
+            let ports = free_ports(3)?;
+            for (key, port) in [SERVER_PORT, RCON_PORT, QUERY_PORT].iter().zip(ports) {
+                self.properties
+                    .entry(key.to_string())
+                    .or_insert_with(|| port.to_string());
+            }
+        }
+
+        if self.enable_rcon {
+            if !self.properties.contains_key(RCON_PORT) {
+                let port = free_ports(1)?[0];
+                self.properties
+                    .insert(RCON_PORT.to_string(), port.to_string());
+            }
+            self.properties
+                .insert(ENABLE_RCON.to_string(), "true".to_string());
+            self.properties
+                .entry(RCON_PASSWORD.to_string())
+                .or_insert_with(random_password);
+        }
+
+        self.auto_ports = false;
+        self.enable_rcon = false;
+        Ok(())
+    }
+}
+
+/// Returns true for the line printed once the server finished loading
+fn is_done_line(line: &str) -> bool {
+    line.contains("[Server thread/INFO]: Done ")
+}
+
+/// Generates a random alphanumeric password for rcon
+fn random_password() -> String {
+    const PASSWORD_LENGTH: usize = 16;
+
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(PASSWORD_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ServerBuilder, ENABLE_RCON, QUERY_PORT, RCON_PASSWORD, RCON_PORT, SERVER_PORT};
+
+    #[test]
+    fn test_auto_ports_keep_explicit_ports() {
+        let mut builder = ServerBuilder::new("server")
+            .property(RCON_PORT, "25575")
+            .auto_ports();
+        builder.resolve_properties().unwrap();
+
+        assert_eq!(builder.properties[RCON_PORT], "25575");
+        assert!(builder.properties[SERVER_PORT].parse::<u16>().is_ok());
+        assert!(builder.properties[QUERY_PORT].parse::<u16>().is_ok());
+    }
+
+    #[test]
+    fn test_enable_rcon_keeps_explicit_password() {
+        let mut builder = ServerBuilder::new("server")
+            .property(RCON_PASSWORD, "secret")
+            .enable_rcon();
+        builder.resolve_properties().unwrap();
+
+        assert_eq!(builder.properties[ENABLE_RCON], "true");
+        assert_eq!(builder.properties[RCON_PASSWORD], "secret");
+        assert!(builder.properties[RCON_PORT].parse::<u16>().is_ok());
+    }
 }
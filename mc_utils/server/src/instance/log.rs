@@ -0,0 +1,188 @@
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The number of lines which are kept by default, older lines are dropped
+const MAX_LINES: usize = 100_000;
+
+#[derive(Debug)]
+struct LogState {
+    lines: VecDeque<String>,
+    /// The index of the first kept line
+    first: usize,
+    capacity: usize,
+    /// Set once the server closed its output
+    closed: bool,
+}
+
+impl Default for LogState {
+    fn default() -> Self {
+        LogState {
+            lines: VecDeque::new(),
+            first: 0,
+            capacity: MAX_LINES,
+            closed: false,
+        }
+    }
+}
+
+impl LogState {
+    /// The total number of lines, including the dropped ones
+    fn len(&self) -> usize {
+        self.first + self.lines.len()
+    }
+
+    fn get(&self, index: usize) -> Option<&String> {
+        self.lines.get(index.checked_sub(self.first)?)
+    }
+}
+
+/// The console output of a server, which is collected in a background thread
+///
+/// Lines are indexed in the order they were printed. Only the latest lines are kept,
+/// so very old lines are no longer returned.
+#[derive(Debug, Clone, Default)]
+pub struct ServerLog {
+    state: Arc<(Mutex<LogState>, Condvar)>,
+}
+
+impl ServerLog {
+    /// Starts collecting the lines of 'output'
+    pub fn spawn_reader(output: impl Read + Send + 'static) -> Self {
+        ServerLog::spawn_reader_with(output, |_| ())
+    }
+
+    /// Like [`ServerLog::spawn_reader`], but also passes every line to 'on_line'
+    pub fn spawn_reader_with(
+        output: impl Read + Send + 'static,
+        mut on_line: impl FnMut(&str) + Send + 'static,
+    ) -> Self {
+        let log = ServerLog::default();
+
+        let writer = log.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(output).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                on_line(&line);
+                writer.push(line);
+            }
+            writer.close();
+        });
+
+        log
+    }
+
+    /// The number of lines collected so far, including dropped lines
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns all kept lines starting at index 'from'
+    pub fn lines_since(&self, from: usize) -> Vec<String> {
+        let state = self.lock();
+        let skip = from.saturating_sub(state.first);
+        state.lines.iter().skip(skip).cloned().collect()
+    }
+
+    /// Waits for a line at or after index 'from' which matches 'predicate'
+    ///
+    /// Returns the index of the line, or `None` if the server closed its output or the timeout expired.
+    pub fn wait_for(
+        &self,
+        from: usize,
+        timeout: Option<Duration>,
+        predicate: impl Fn(&str) -> bool,
+    ) -> Option<usize> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let (_, condvar) = &*self.state;
+
+        let mut state = self.lock();
+        let mut checked = from;
+        loop {
+            let found = (checked..state.len())
+                .find(|&index| state.get(index).is_some_and(|line| predicate(line)));
+            if let Some(index) = found {
+                return Some(index);
+            }
+            checked = checked.max(state.len());
+
+            if state.closed {
+                return None;
+            }
+
+            state = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.checked_duration_since(Instant::now())?;
+                    condvar.wait_timeout(state, remaining).unwrap().0
+                }
+                None => condvar.wait(state).unwrap(),
+            };
+        }
+    }
+
+    fn push(&self, line: String) {
+        let mut state = self.lock();
+        if state.lines.len() >= state.capacity {
+            state.lines.pop_front();
+            state.first += 1;
+        }
+        state.lines.push_back(line);
+        drop(state);
+        self.state.1.notify_all();
+    }
+
+    fn close(&self) {
+        self.lock().closed = true;
+        self.state.1.notify_all();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, LogState> {
+        self.state.0.lock().expect("Log reader panicked")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::ServerLog;
+
+    #[test]
+    fn test_wait_for_line() {
+        let output: &[u8] = b"Starting\n[Server thread/INFO]: Done (1.0s)!\nStopping\n";
+        let log = ServerLog::spawn_reader(output);
+
+        let done = log.wait_for(0, None, |line| line.contains("Done "));
+        assert_eq!(done, Some(1));
+
+        let missing = log.wait_for(0, Some(Duration::from_secs(5)), |line| line == "Crash");
+        assert_eq!(missing, None);
+        assert_eq!(log.lines_since(2), vec!["Stopping".to_string()]);
+    }
+
+    #[test]
+    fn test_drop_old_lines() {
+        let log = ServerLog::default();
+        log.lock().capacity = 2;
+        for line in ["first", "second", "third"] {
+            log.push(line.to_string());
+        }
+        log.close();
+
+        assert_eq!(log.len(), 3);
+        assert_eq!(log.lines_since(0), vec!["second", "third"]);
+        assert_eq!(log.lines_since(2), vec!["third"]);
+        assert_eq!(log.wait_for(0, None, |line| line != "first"), Some(1));
+    }
+}
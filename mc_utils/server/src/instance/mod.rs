@@ -5,4 +5,10 @@ mod implementation;
 pub use implementation::{ServerBuilder, ServerInstance};
 
 mod handle;
-pub use handle::run_server;
+pub use handle::{java_version, run_server};
+
+mod ports;
+pub use ports::free_ports;
+
+mod log;
+pub use log::ServerLog;
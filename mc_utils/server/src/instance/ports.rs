@@ -0,0 +1,31 @@
+use std::net::{Ipv4Addr, TcpListener};
+
+/// Finds `count` distinct ports which are currently not in use
+///
+/// The ports are only guaranteed to be free at the time of calling this function,
+/// so they should be used as soon as possible.
+pub fn free_ports(count: usize) -> std::io::Result<Vec<u16>> {
+    // Keep all listeners alive until every port is found, so that no port is returned twice
+    let listeners = (0..count)
+        .map(|_| TcpListener::bind((Ipv4Addr::LOCALHOST, 0)))
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    listeners
+        .iter()
+        .map(|listener| Ok(listener.local_addr()?.port()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::free_ports;
+
+    #[test]
+    fn test_free_ports_are_distinct() {
+        let mut ports = free_ports(3).unwrap();
+        ports.sort_unstable();
+        ports.dedup();
+
+        assert_eq!(ports.len(), 3);
+    }
+}
@@ -0,0 +1,421 @@
+//! Reads and extracts files from server and client jars
+//!
+//! Since 1.18 the server jar is a bundler, which contains the actual server jar in
+//! `META-INF/versions/` and its libraries in `META-INF/libraries/`. Both are listed
+//! in `META-INF/versions.list` and `META-INF/libraries.list` as `<sha256>\t<id>\t<path>`.
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, Cursor, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Deserializer};
+use zip::{result::ZipError, ZipArchive};
+
+const VERSIONS_LIST: &str = "META-INF/versions.list";
+const LIBRARIES_LIST: &str = "META-INF/libraries.list";
+
+/// A jar opened for reading
+pub struct JarFile<R> {
+    archive: ZipArchive<R>,
+}
+
+/// A jar inside of a bundler jar
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundledFile {
+    /// The sha256 hash of the file
+    pub sha256: String,
+    /// The version or the maven coordinates of a library
+    pub id: String,
+    /// The path relative to `META-INF/versions/` or `META-INF/libraries/`
+    pub path: String,
+}
+
+/// The files extracted by [`JarFile::unbundle`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unbundled {
+    pub server_jar: PathBuf,
+    pub libraries: Vec<PathBuf>,
+}
+
+/// The `version.json` embedded in server and client jars since 1.14
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct JarVersion {
+    pub id: String,
+    pub name: String,
+    /// The data version which is written to the `DataVersion` of worlds
+    pub world_version: u32,
+    pub protocol_version: u32,
+    pub pack_version: PackVersion,
+    /// The minimum major java version, only known since 1.17
+    pub java_version: Option<u32>,
+    /// Whether this is a release
+    pub stable: bool,
+}
+
+/// The resource pack and data pack formats of a version
+///
+/// Before 1.19.4 both formats were always the same and stored as a single number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackVersion {
+    pub resource: u32,
+    pub data: u32,
+}
+
+impl<'de> Deserialize<'de> for PackVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawPackVersion {
+            Single(u32),
+            Split {
+                #[serde(alias = "resource_major")]
+                resource: u32,
+                #[serde(alias = "data_major")]
+                data: u32,
+            },
+        }
+
+        Ok(match RawPackVersion::deserialize(deserializer)? {
+            RawPackVersion::Single(version) => PackVersion {
+                resource: version,
+                data: version,
+            },
+            RawPackVersion::Split { resource, data } => PackVersion { resource, data },
+        })
+    }
+}
+
+impl JarFile<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        JarFile::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl JarFile<Cursor<Vec<u8>>> {
+    pub fn from_bytes(bytes: Vec<u8>) -> io::Result<Self> {
+        JarFile::new(Cursor::new(bytes))
+    }
+}
+
+impl<R: Read + Seek> JarFile<R> {
+    pub fn new(reader: R) -> io::Result<Self> {
+        Ok(JarFile {
+            archive: ZipArchive::new(reader).map_err(invalid_data)?,
+        })
+    }
+
+    pub fn file_names(&self) -> impl Iterator<Item = &str> {
+        self.archive.file_names()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.archive.index_for_name(name).is_some()
+    }
+
+    pub fn read_file(&mut self, name: &str) -> io::Result<Vec<u8>> {
+        let mut file = self.archive.by_name(name).map_err(|error| match error {
+            ZipError::FileNotFound => io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} does not exist in the jar", name),
+            ),
+            error => invalid_data(error),
+        })?;
+        let mut content = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut content)?;
+        Ok(content)
+    }
+
+    pub fn read_to_string(&mut self, name: &str) -> io::Result<String> {
+        String::from_utf8(self.read_file(name)?).map_err(invalid_data)
+    }
+
+    /// Reads the `version.json` which describes the version of a server or client jar
+    ///
+    /// Fails with [`io::ErrorKind::NotFound`] for jars older than 1.14.
+    pub fn version_json(&mut self) -> io::Result<JarVersion> {
+        Ok(serde_json::from_slice(&self.read_file("version.json")?)?)
+    }
+
+    /// Extracts all files whose name starts with 'prefix' into 'destination'
+    ///
+    /// The files keep their path within the jar, e.g. the prefix `data/minecraft/` extracts
+    /// the vanilla datapack to `destination/data/minecraft/`. Returns the extracted files.
+    pub fn extract(
+        &mut self,
+        prefix: &str,
+        destination: impl AsRef<Path>,
+    ) -> io::Result<Vec<PathBuf>> {
+        let mut extracted = Vec::new();
+        for index in 0..self.archive.len() {
+            let mut file = self.archive.by_index(index).map_err(invalid_data)?;
+            if !file.name().starts_with(prefix) || file.is_dir() {
+                continue;
+            }
+            // Entries which would escape the destination are skipped
+            let relative = match file.enclosed_name() {
+                Some(relative) => relative,
+                None => continue,
+            };
+
+            let path = destination.as_ref().join(relative);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            io::copy(&mut file, &mut File::create(&path)?)?;
+            extracted.push(path);
+        }
+        Ok(extracted)
+    }
+
+    /// Whether this is a bundler jar which contains the actual server jar
+    pub fn is_bundler(&self) -> bool {
+        self.contains(VERSIONS_LIST)
+    }
+
+    /// The server jars contained in a bundler jar
+    pub fn bundled_versions(&mut self) -> io::Result<Vec<BundledFile>> {
+        self.read_bundle_list(VERSIONS_LIST)
+    }
+
+    /// The libraries contained in a bundler jar
+    pub fn bundled_libraries(&mut self) -> io::Result<Vec<BundledFile>> {
+        if !self.contains(LIBRARIES_LIST) {
+            return Ok(Vec::new());
+        }
+        self.read_bundle_list(LIBRARIES_LIST)
+    }
+
+    /// Opens the server jar contained in a bundler jar in memory
+    pub fn bundled_server(&mut self) -> io::Result<JarFile<Cursor<Vec<u8>>>> {
+        let version = self.bundled_server_file()?;
+        JarFile::from_bytes(self.read_file(&format!("META-INF/versions/{}", version.path))?)
+    }
+
+    /// Extracts the server jar and the libraries of a bundler jar into 'destination'
+    ///
+    /// The server jar is written to `versions/` and the libraries to `libraries/`,
+    /// like the bundler does when it starts.
+    pub fn unbundle(&mut self, destination: impl AsRef<Path>) -> io::Result<Unbundled> {
+        let destination = destination.as_ref();
+        let version = self.bundled_server_file()?;
+        let server_jar = self.extract_bundled(
+            "META-INF/versions/",
+            &version,
+            &destination.join("versions"),
+        )?;
+
+        let mut libraries = Vec::new();
+        for library in self.bundled_libraries()? {
+            libraries.push(self.extract_bundled(
+                "META-INF/libraries/",
+                &library,
+                &destination.join("libraries"),
+            )?);
+        }
+
+        Ok(Unbundled {
+            server_jar,
+            libraries,
+        })
+    }
+
+    fn bundled_server_file(&mut self) -> io::Result<BundledFile> {
+        self.bundled_versions()?.into_iter().next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "The jar is not a bundler or contains no server jar",
+            )
+        })
+    }
+
+    fn extract_bundled(
+        &mut self,
+        prefix: &str,
+        file: &BundledFile,
+        destination: &Path,
+    ) -> io::Result<PathBuf> {
+        if Path::new(&file.path)
+            .components()
+            .any(|component| !matches!(component, std::path::Component::Normal(_)))
+        {
+            return Err(invalid_data(format!("Invalid bundled path {}", file.path)));
+        }
+
+        let path = destination.join(&file.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = self.read_file(&format!("{}{}", prefix, file.path))?;
+        fs::write(&path, content)?;
+        Ok(path)
+    }
+
+    fn read_bundle_list(&mut self, name: &str) -> io::Result<Vec<BundledFile>> {
+        self.read_to_string(name)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let mut parts = line.split('\t');
+                match (parts.next(), parts.next(), parts.next()) {
+                    (Some(sha256), Some(id), Some(path)) => Ok(BundledFile {
+                        sha256: sha256.to_string(),
+                        id: id.to_string(),
+                        path: path.to_string(),
+                    }),
+                    _ => Err(invalid_data(format!("Invalid line in {}: {}", name, line))),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Extracts all files starting with 'prefix' from the server jar at 'path' into 'destination'
+///
+/// Bundler jars are looked into, so this works for all server jars, e.g. with the prefixes
+/// `data/minecraft/` for the vanilla datapack or `assets/minecraft/lang/` for the english language file.
+pub fn extract_server_files(
+    path: impl AsRef<Path>,
+    prefix: &str,
+    destination: impl AsRef<Path>,
+) -> io::Result<Vec<PathBuf>> {
+    let mut jar = JarFile::open(path)?;
+    if jar.is_bundler() {
+        jar.bundled_server()?.extract(prefix, destination)
+    } else {
+        jar.extract(prefix, destination)
+    }
+}
+
+/// Reads the version of the server or client jar at 'path'
+pub fn read_jar_version(path: impl AsRef<Path>) -> io::Result<JarVersion> {
+    JarFile::open(path)?.version_json()
+}
+
+fn invalid_data(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        io::{Cursor, Write},
+        path::Path,
+    };
+
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use super::{extract_server_files, read_jar_version, JarFile, PackVersion};
+
+    const VERSION_JSON: &str = r#"{
+        "id": "1.20.4",
+        "name": "1.20.4",
+        "world_version": 3700,
+        "series_id": "main",
+        "protocol_version": 765,
+        "pack_version": {"resource": 22, "data": 26},
+        "build_time": "2023-12-07T08:52:54+00:00",
+        "java_component": "java-runtime-gamma",
+        "java_version": 17,
+        "stable": true,
+        "use_editor": false
+    }"#;
+
+    fn write_jar(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn write_bundler(path: &Path) {
+        let server = write_jar(&[
+            ("version.json", VERSION_JSON.as_bytes()),
+            ("data/minecraft/tags/blocks/logs.json", b"{}"),
+            ("assets/minecraft/lang/en_us.json", b"{}"),
+            ("net/minecraft/server/Main.class", b""),
+        ]);
+        let bundler = write_jar(&[
+            ("version.json", VERSION_JSON.as_bytes()),
+            ("META-INF/versions.list", b"abc\t1.20.4\t1.20.4/server-1.20.4.jar\n"),
+            ("META-INF/versions/1.20.4/server-1.20.4.jar", &server),
+            ("META-INF/libraries.list", b"def\tcom.google.code.gson:gson:2.10.1\tcom/google/code/gson/gson/2.10.1/gson-2.10.1.jar\n"),
+            ("META-INF/libraries/com/google/code/gson/gson/2.10.1/gson-2.10.1.jar", b"gson"),
+        ]);
+        fs::write(path, bundler).unwrap();
+    }
+
+    #[test]
+    fn test_unbundle() {
+        let dir = tempfile::tempdir().unwrap();
+        let jar_path = dir.path().join("server.jar");
+        write_bundler(&jar_path);
+
+        let mut jar = JarFile::open(&jar_path).unwrap();
+        assert!(jar.is_bundler());
+        assert_eq!(jar.version_json().unwrap().id, "1.20.4");
+
+        let unbundled = jar.unbundle(dir.path().join("out")).unwrap();
+        assert_eq!(
+            unbundled.server_jar,
+            dir.path().join("out/versions/1.20.4/server-1.20.4.jar")
+        );
+        assert_eq!(fs::read(&unbundled.libraries[0]).unwrap(), b"gson");
+
+        let server = JarFile::open(&unbundled.server_jar).unwrap();
+        assert!(!server.is_bundler());
+        assert!(server.contains("net/minecraft/server/Main.class"));
+    }
+
+    #[test]
+    fn test_read_jar_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let jar_path = dir.path().join("server.jar");
+        write_bundler(&jar_path);
+
+        let version = read_jar_version(&jar_path).unwrap();
+        assert_eq!(version.world_version, 3700);
+        assert_eq!(version.protocol_version, 765);
+        assert_eq!(
+            version.pack_version,
+            PackVersion {
+                resource: 22,
+                data: 26
+            }
+        );
+        assert_eq!(version.java_version, Some(17));
+        assert!(version.stable);
+
+        // 1.16.5 has a single pack version and no java version
+        let old: super::JarVersion = serde_json::from_str(
+            r#"{"id": "1.16.5", "name": "1.16.5", "world_version": 2586, "protocol_version": 754, "pack_version": 6, "stable": true}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            old.pack_version,
+            PackVersion {
+                resource: 6,
+                data: 6
+            }
+        );
+        assert_eq!(old.java_version, None);
+    }
+
+    #[test]
+    fn test_extract_server_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let jar_path = dir.path().join("server.jar");
+        write_bundler(&jar_path);
+
+        let extracted =
+            extract_server_files(&jar_path, "data/minecraft/", dir.path().join("out")).unwrap();
+
+        assert_eq!(
+            extracted,
+            vec![dir.path().join("out/data/minecraft/tags/blocks/logs.json")]
+        );
+    }
+}
@@ -1,8 +1,69 @@
+mod artifacts;
+mod assets;
+mod backup;
+mod cache;
+mod datapack;
+mod details;
+pub mod distribution;
+mod download;
+mod gametest;
+mod http;
 mod instance;
+mod jar;
+mod lists;
+mod mappings;
+mod mods;
+mod supervisor;
+#[cfg(test)]
+mod test_util;
+mod uuid;
 mod version;
+mod version_id;
+mod version_table;
+mod world;
 
-pub use instance::{run_server, ServerInstance};
+pub use artifacts::{download_artifact, download_libraries, ArtifactKind};
+pub use assets::{AssetDownloader, AssetIndexFile, AssetObject, RESOURCES_URL};
+pub use backup::{restore_backup, Backup, Backups, RetentionPolicy};
+pub use cache::{Cache, DEFAULT_MANIFEST_TTL};
+pub use datapack::{install_datapack_files, parse_enabled_datapacks, DATAPACKS_DIR};
+pub use details::{
+    rules_allow, Argument, ArgumentValue, Arguments, Artifact, AssetIndex, Download, Downloads,
+    JavaVersion, Library, LibraryDownloads, Logging, LoggingConfig, LoggingFile, OsRule, Rule,
+    RuleAction, RuleContext, VersionDetails,
+};
+pub use download::{
+    download_verified, sha1_file, CancelToken, Checksum, DownloadError, Downloader, Progress,
+    ProgressCallback,
+};
+pub use gametest::{GameTestMode, GameTestRunner, TestReport, TestResult, GAMETEST_MAIN_CLASS};
+pub use http::{HttpBackend, HttpClient, HttpRequest, HttpResponse, StubBackend, UreqBackend};
+pub use instance::{
+    free_ports, java_version, run_server, InstanceError, ServerBuilder, ServerInstance, ServerLog,
+};
+pub use jar::{
+    extract_server_files, read_jar_version, BundledFile, JarFile, JarVersion, PackVersion,
+    Unbundled,
+};
+pub use lists::{
+    read_list, write_list, BanInfo, BannedIpEntry, BannedPlayerEntry, ListEntry, OpEntry,
+    WhitelistEntry, BANNED_IPS_JSON, BANNED_PLAYERS_JSON, OPS_JSON, WHITELIST_JSON,
+};
+pub use mappings::{ClassMapping, FieldMapping, Mappings, MethodMapping};
+pub use mods::{
+    check_dependencies, check_server_mods, list_mods, read_mod_metadata, Dependency,
+    DependencyKind, DependencyProblem, ModFormat, ModList, ModMetadata, Platform, MODS_DIR,
+    PLUGINS_DIR,
+};
+pub use supervisor::{
+    latest_crash_report, CrashReport, RestartPolicy, ServerState, Supervisor, CRASH_REPORTS_DIR,
+};
+pub use uuid::{MojangUuidLookup, ParseUuidError, Uuid, UuidLookup, PROFILE_LOOKUP_URL};
 pub use version::{
-    download_server, LatestVersions, VersionInfo, VersionManifest, VersionType,
-    VERSION_MANIFEST_URL,
+    download_server, download_server_cached, download_server_with, LatestVersions, ManifestError,
+    VersionDownload, VersionInfo, VersionManifest, VersionType, VERSION_MANIFEST_URL,
+    VERSION_MANIFEST_V2_URL,
 };
+pub use version_id::{Release, VersionId};
+pub use version_table::{VersionEntry, VersionTable};
+pub use world::{install_world_template, read_data_version};
@@ -0,0 +1,277 @@
+//! Typed access to the player and ip lists of a server
+//!
+//! A server keeps `whitelist.json`, `ops.json`, `banned-players.json` and `banned-ips.json`
+//! next to its `server.properties`. These files can be edited while the server is offline
+//! with [`read_list`] and [`write_list`], or changed on a running [`ServerInstance`] by
+//! issuing the matching commands.
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use chrono::{DateTime, FixedOffset};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    instance::{Result, ServerInstance},
+    Uuid,
+};
+
+pub const WHITELIST_JSON: &str = "whitelist.json";
+pub const OPS_JSON: &str = "ops.json";
+pub const BANNED_PLAYERS_JSON: &str = "banned-players.json";
+pub const BANNED_IPS_JSON: &str = "banned-ips.json";
+
+/// The date format used by minecraft for the `created` and `expires` fields
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S %z";
+/// The value of `expires` for bans that never expire
+const EXPIRES_FOREVER: &str = "forever";
+
+/// An entry of one of the server list files
+pub trait ListEntry: Serialize + DeserializeOwned {
+    /// The name of the file inside the server directory
+    const FILE_NAME: &'static str;
+}
+
+/// A player who is allowed to join when the whitelist is enabled
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WhitelistEntry {
+    pub uuid: Uuid,
+    pub name: String,
+}
+
+impl ListEntry for WhitelistEntry {
+    const FILE_NAME: &'static str = WHITELIST_JSON;
+}
+
+/// A player with operator permissions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpEntry {
+    pub uuid: Uuid,
+    pub name: String,
+    /// The permission level, between 1 and 4
+    pub level: u8,
+    pub bypasses_player_limit: bool,
+}
+
+impl ListEntry for OpEntry {
+    const FILE_NAME: &'static str = OPS_JSON;
+}
+
+/// Information shared by player bans and ip bans
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BanInfo {
+    #[serde(
+        serialize_with = "serialize_date",
+        deserialize_with = "deserialize_date"
+    )]
+    pub created: DateTime<FixedOffset>,
+    /// Who issued the ban, `Server` if it was issued from the console
+    pub source: String,
+    /// When the ban expires, `None` if it never does
+    #[serde(
+        serialize_with = "serialize_expires",
+        deserialize_with = "deserialize_expires"
+    )]
+    pub expires: Option<DateTime<FixedOffset>>,
+    pub reason: String,
+}
+
+/// A banned player
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BannedPlayerEntry {
+    pub uuid: Uuid,
+    pub name: String,
+    #[serde(flatten)]
+    pub ban: BanInfo,
+}
+
+impl ListEntry for BannedPlayerEntry {
+    const FILE_NAME: &'static str = BANNED_PLAYERS_JSON;
+}
+
+/// A banned ip address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BannedIpEntry {
+    pub ip: String,
+    #[serde(flatten)]
+    pub ban: BanInfo,
+}
+
+impl ListEntry for BannedIpEntry {
+    const FILE_NAME: &'static str = BANNED_IPS_JSON;
+}
+
+/// Reads the list of `T` from the server directory at 'dir'
+///
+/// Returns an empty list if the file does not exist yet
+pub fn read_list<T: ListEntry>(dir: impl AsRef<Path>) -> Result<Vec<T>> {
+    let path = dir.as_ref().join(T::FILE_NAME);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let file = File::open(path)?;
+    Ok(serde_json::from_reader(BufReader::new(file))?)
+}
+
+/// Overwrites the list of `T` in the server directory at 'dir'
+///
+/// The server only reads these files on startup, so this should not be used on a running server.
+pub fn write_list<T: ListEntry>(dir: impl AsRef<Path>, entries: &[T]) -> Result<()> {
+    let file = File::create(dir.as_ref().join(T::FILE_NAME))?;
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer_pretty(&mut writer, entries)?;
+    writer.flush()?;
+    Ok(())
+}
+
+impl ServerInstance {
+    /// Reads the list of `T` of this server
+    ///
+    /// The server writes changes to these files immediately, so this can be used to inspect
+    /// the effects of the list commands.
+    pub fn read_list<T: ListEntry>(&self) -> Result<Vec<T>> {
+        read_list(&self.dir)
+    }
+
+    /// Adds a player to the whitelist
+    pub fn whitelist_add(&mut self, name: &str) -> Result<()> {
+        self.command(&format!("whitelist add {}", name))
+    }
+
+    /// Removes a player from the whitelist
+    pub fn whitelist_remove(&mut self, name: &str) -> Result<()> {
+        self.command(&format!("whitelist remove {}", name))
+    }
+
+    /// Grants operator permissions to a player
+    pub fn op(&mut self, name: &str) -> Result<()> {
+        self.command(&format!("op {}", name))
+    }
+
+    /// Revokes the operator permissions of a player
+    pub fn deop(&mut self, name: &str) -> Result<()> {
+        self.command(&format!("deop {}", name))
+    }
+
+    /// Bans a player, optionally with a reason
+    pub fn ban(&mut self, name: &str, reason: Option<&str>) -> Result<()> {
+        match reason {
+            Some(reason) => self.command(&format!("ban {} {}", name, reason)),
+            None => self.command(&format!("ban {}", name)),
+        }
+    }
+
+    /// Removes a player from the ban list
+    pub fn pardon(&mut self, name: &str) -> Result<()> {
+        self.command(&format!("pardon {}", name))
+    }
+
+    /// Bans an ip address, optionally with a reason
+    pub fn ban_ip(&mut self, ip: &str, reason: Option<&str>) -> Result<()> {
+        match reason {
+            Some(reason) => self.command(&format!("ban-ip {} {}", ip, reason)),
+            None => self.command(&format!("ban-ip {}", ip)),
+        }
+    }
+
+    /// Removes an ip address from the ban list
+    pub fn pardon_ip(&mut self, ip: &str) -> Result<()> {
+        self.command(&format!("pardon-ip {}", ip))
+    }
+}
+
+fn serialize_date<S>(date: &DateTime<FixedOffset>, ser: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    ser.collect_str(&date.format(DATE_FORMAT))
+}
+
+fn deserialize_date<'de, D>(de: D) -> std::result::Result<DateTime<FixedOffset>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    let date_str = String::deserialize(de)?;
+    DateTime::parse_from_str(&date_str, DATE_FORMAT)
+        .map_err(|_| D::Error::custom("Could not parse date"))
+}
+
+fn serialize_expires<S>(
+    date: &Option<DateTime<FixedOffset>>,
+    ser: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match date {
+        Some(date) => serialize_date(date, ser),
+        None => ser.serialize_str(EXPIRES_FOREVER),
+    }
+}
+
+fn deserialize_expires<'de, D>(
+    de: D,
+) -> std::result::Result<Option<DateTime<FixedOffset>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    let date_str = String::deserialize(de)?;
+    if date_str == EXPIRES_FOREVER {
+        return Ok(None);
+    }
+    DateTime::parse_from_str(&date_str, DATE_FORMAT)
+        .map(Some)
+        .map_err(|_| D::Error::custom("Could not parse date"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_list, write_list, BannedIpEntry, BannedPlayerEntry, OpEntry};
+    use crate::Uuid;
+
+    #[test]
+    fn test_read_ops() {
+        let ops: Vec<OpEntry> = serde_json::from_str(
+            r#"[{"uuid": "069a79f4-44e9-4726-a5be-fca90e38aaf5", "name": "Notch", "level": 4, "bypassesPlayerLimit": false}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(ops[0].name, "Notch");
+        assert_eq!(ops[0].level, 4);
+        assert!(!ops[0].bypasses_player_limit);
+    }
+
+    #[test]
+    fn test_ban_roundtrip() {
+        let json = r#"{"uuid":"069a79f4-44e9-4726-a5be-fca90e38aaf5","name":"Notch","created":"2023-05-01 12:30:00 +0200","source":"Server","expires":"forever","reason":"Banned by an operator."}"#;
+        let entry: BannedPlayerEntry = serde_json::from_str(json).unwrap();
+
+        assert_eq!(entry.ban.expires, None);
+        assert_eq!(entry.ban.source, "Server");
+        assert_eq!(serde_json::to_string(&entry).unwrap(), json);
+    }
+
+    #[test]
+    fn test_write_and_read_list() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(read_list::<BannedIpEntry>(dir.path()).unwrap().is_empty());
+
+        let ops = vec![OpEntry {
+            uuid: Uuid::offline("Notch"),
+            name: "Notch".to_string(),
+            level: 2,
+            bypasses_player_limit: true,
+        }];
+        write_list(dir.path(), &ops).unwrap();
+
+        assert_eq!(read_list::<OpEntry>(dir.path()).unwrap(), ops);
+    }
+}
@@ -0,0 +1,387 @@
+//! Parses the official ProGuard mappings and translates obfuscated names
+//!
+//! The mappings list every class as `original -> obfuscated:`, followed by its
+//! indented fields and methods. Methods may be prefixed with the range of obfuscated lines
+//! they occupy and suffixed with the corresponding original lines.
+use std::{collections::HashMap, fs, io, path::Path};
+
+use crate::supervisor::CrashReport;
+
+/// The mappings of a single jar
+#[derive(Debug, Clone, Default)]
+pub struct Mappings {
+    classes: Vec<ClassMapping>,
+    by_original: HashMap<String, usize>,
+    by_obfuscated: HashMap<String, usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassMapping {
+    pub original: String,
+    pub obfuscated: String,
+    pub fields: Vec<FieldMapping>,
+    pub methods: Vec<MethodMapping>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldMapping {
+    /// The original type of the field
+    pub typ: String,
+    pub original: String,
+    pub obfuscated: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodMapping {
+    pub return_type: String,
+    pub original: String,
+    /// The original argument types
+    pub arguments: Vec<String>,
+    pub obfuscated: String,
+    /// The lines in the obfuscated jar, inclusive
+    pub obfuscated_lines: Option<(u32, u32)>,
+    /// The lines in the original source, inclusive
+    pub original_lines: Option<(u32, u32)>,
+}
+
+impl Mappings {
+    pub fn parse(content: &str) -> io::Result<Self> {
+        let mut mappings = Mappings::default();
+
+        for (index, line) in content.lines().enumerate() {
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid mapping in line {}: {}", index + 1, line),
+                )
+            };
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+
+            let (original, obfuscated) = line.trim().split_once(" -> ").ok_or_else(invalid)?;
+            if !line.starts_with(char::is_whitespace) {
+                let obfuscated = obfuscated.strip_suffix(':').ok_or_else(invalid)?;
+                mappings.classes.push(ClassMapping {
+                    original: original.to_string(),
+                    obfuscated: obfuscated.to_string(),
+                    fields: Vec::new(),
+                    methods: Vec::new(),
+                });
+                continue;
+            }
+
+            let class = mappings.classes.last_mut().ok_or_else(invalid)?;
+            if original.contains('(') {
+                let method = parse_method(original, obfuscated).ok_or_else(invalid)?;
+                class.methods.push(method);
+            } else {
+                let (typ, original) = original.split_once(' ').ok_or_else(invalid)?;
+                class.fields.push(FieldMapping {
+                    typ: typ.to_string(),
+                    original: original.to_string(),
+                    obfuscated: obfuscated.to_string(),
+                });
+            }
+        }
+
+        for (index, class) in mappings.classes.iter().enumerate() {
+            mappings.by_original.insert(class.original.clone(), index);
+            mappings
+                .by_obfuscated
+                .insert(class.obfuscated.clone(), index);
+        }
+        Ok(mappings)
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> io::Result<Self> {
+        Mappings::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn classes(&self) -> &[ClassMapping] {
+        &self.classes
+    }
+
+    /// Finds a class by its original name, e.g. `net.minecraft.server.MinecraftServer`
+    pub fn class(&self, original: &str) -> Option<&ClassMapping> {
+        self.by_original
+            .get(original)
+            .map(|index| &self.classes[*index])
+    }
+
+    pub fn class_by_obfuscated(&self, obfuscated: &str) -> Option<&ClassMapping> {
+        self.by_obfuscated
+            .get(obfuscated)
+            .map(|index| &self.classes[*index])
+    }
+
+    pub fn deobfuscate_class(&self, obfuscated: &str) -> Option<&str> {
+        self.class_by_obfuscated(obfuscated)
+            .map(|class| class.original.as_str())
+    }
+
+    pub fn obfuscate_class(&self, original: &str) -> Option<&str> {
+        self.class(original).map(|class| class.obfuscated.as_str())
+    }
+
+    /// Replaces the obfuscated names in a stack trace with the original names
+    ///
+    /// Frames like `at abc.a(SourceFile:12)` get their class, method and line remapped,
+    /// exception names at the start of a line get their class remapped.
+    /// Everything else is left unchanged.
+    pub fn remap_stack_trace(&self, text: &str) -> String {
+        let mut remapped = String::with_capacity(text.len());
+        for line in text.split_inclusive('\n') {
+            let content = line.trim_end_matches(['\r', '\n']);
+            let ending = &line[content.len()..];
+            let indent = &content[..content.len() - content.trim_start().len()];
+            let trimmed = content.trim_start();
+
+            let new_content = match trimmed.strip_prefix("at ") {
+                Some(frame) => self
+                    .remap_frame(frame)
+                    .map(|frame| format!("{}at {}", indent, frame)),
+                None => self
+                    .remap_exception(trimmed)
+                    .map(|line| indent.to_string() + &line),
+            };
+            remapped.push_str(new_content.as_deref().unwrap_or(content));
+            remapped.push_str(ending);
+        }
+        remapped
+    }
+
+    /// Remaps a frame like `abc.a(SourceFile:12)`, which may be prefixed by a module
+    ///
+    /// Anything after the closing parenthesis, like the jar `~[server-1.20.4.jar:?]`
+    /// added by log4j, is kept.
+    fn remap_frame(&self, frame: &str) -> Option<String> {
+        let (location, rest) = frame.split_once('(')?;
+        let (source, suffix) = rest.split_once(')')?;
+        let (module, location) = match location.rsplit_once('/') {
+            Some((module, location)) => (Some(module), location),
+            None => (None, location),
+        };
+        let (class_name, method_name) = location.rsplit_once('.')?;
+        let class = self.class_by_obfuscated(class_name)?;
+
+        let line = source
+            .rsplit_once(':')
+            .and_then(|(_, line)| line.parse::<u32>().ok());
+        let (method_name, line) = match class.method_at(method_name, line) {
+            Some(method) => (
+                method.original.as_str(),
+                line.map(|line| method.original_line(line)),
+            ),
+            None => (method_name, line),
+        };
+
+        let file = source.split(':').next().unwrap_or(source);
+        let source = match line {
+            Some(line) => format!("{}:{}", file, line),
+            None => source.to_string(),
+        };
+        let module = module.map(|module| format!("{}/", module));
+        Some(format!(
+            "{}{}.{}({}){}",
+            module.unwrap_or_default(),
+            class.original,
+            method_name,
+            source,
+            suffix
+        ))
+    }
+
+    /// Remaps lines like `abc: message` or `Caused by: abc: message`
+    fn remap_exception(&self, line: &str) -> Option<String> {
+        let (prefix, rest) = match line.strip_prefix("Caused by: ") {
+            Some(rest) => ("Caused by: ", rest),
+            None => ("", line),
+        };
+        let end = rest.find(':').unwrap_or(rest.len());
+        let class = self.deobfuscate_class(&rest[..end])?;
+        Some(format!("{}{}{}", prefix, class, &rest[end..]))
+    }
+}
+
+impl ClassMapping {
+    pub fn field_by_obfuscated(&self, obfuscated: &str) -> Option<&FieldMapping> {
+        self.fields
+            .iter()
+            .find(|field| field.obfuscated == obfuscated)
+    }
+
+    /// All methods with the obfuscated name, which can be overloads of different methods
+    pub fn methods_by_obfuscated<'a>(
+        &'a self,
+        obfuscated: &'a str,
+    ) -> impl Iterator<Item = &'a MethodMapping> {
+        self.methods
+            .iter()
+            .filter(move |method| method.obfuscated == obfuscated)
+    }
+
+    /// Finds the method with the obfuscated name which contains the obfuscated 'line'
+    ///
+    /// Without a line, the method is only found if the name is unambiguous.
+    pub fn method_at(&self, obfuscated: &str, line: Option<u32>) -> Option<&MethodMapping> {
+        let mut candidates = self
+            .methods
+            .iter()
+            .filter(|method| method.obfuscated == obfuscated);
+        match line {
+            Some(line) => {
+                let candidates: Vec<_> = candidates.collect();
+                candidates
+                    .iter()
+                    .find(|method| {
+                        method
+                            .obfuscated_lines
+                            .is_some_and(|(start, end)| (start..=end).contains(&line))
+                    })
+                    .or_else(|| candidates.first())
+                    .copied()
+            }
+            None => {
+                let first = candidates.next()?;
+                candidates
+                    .all(|method| method.original == first.original)
+                    .then_some(first)
+            }
+        }
+    }
+}
+
+impl MethodMapping {
+    /// Translates a line of the obfuscated jar to the original source
+    pub fn original_line(&self, line: u32) -> u32 {
+        match (self.obfuscated_lines, self.original_lines) {
+            (Some((start, end)), Some((original_start, original_end)))
+                if end.checked_sub(start).is_some()
+                    && end.checked_sub(start) == original_end.checked_sub(original_start) =>
+            {
+                original_start.saturating_add(line.saturating_sub(start))
+            }
+            (_, Some((original_start, _))) => original_start,
+            _ => line,
+        }
+    }
+}
+
+impl CrashReport {
+    /// Returns the content of the report with the stack traces remapped to the original names
+    pub fn remap(&self, mappings: &Mappings) -> String {
+        mappings.remap_stack_trace(&self.content)
+    }
+}
+
+/// Parses `1:3:void name(int,java.lang.String):10:12`, where the line numbers are optional
+fn parse_method(method: &str, obfuscated: &str) -> Option<MethodMapping> {
+    let mut rest = method;
+    let mut obfuscated_lines = None;
+    if rest.starts_with(|c: char| c.is_ascii_digit()) {
+        let (start, remainder) = rest.split_once(':')?;
+        let (end, remainder) = remainder.split_once(':')?;
+        obfuscated_lines = Some((start.parse().ok()?, end.parse().ok()?));
+        rest = remainder;
+    }
+
+    let (signature, original_lines) = rest.split_once(')')?;
+    let original_lines = match original_lines.strip_prefix(':') {
+        Some(lines) => {
+            let (start, end) = lines.split_once(':').unwrap_or((lines, lines));
+            Some((start.parse().ok()?, end.parse().ok()?))
+        }
+        None => None,
+    };
+
+    let (declaration, arguments) = signature.split_once('(')?;
+    let (return_type, original) = declaration.split_once(' ')?;
+    let arguments = arguments
+        .split(',')
+        .filter(|argument| !argument.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    Some(MethodMapping {
+        return_type: return_type.to_string(),
+        original: original.to_string(),
+        arguments,
+        obfuscated: obfuscated.to_string(),
+        obfuscated_lines,
+        original_lines,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::Mappings;
+
+    const MAPPINGS: &str = "# {\"fileName\":\"server.txt\",\"id\":\"sourceFile\"}
+net.minecraft.server.MinecraftServer -> abc:
+    int tickCount -> a
+    java.lang.String motd -> b
+    1:1:void <init>() -> <init>
+    10:12:void tickServer(java.util.function.BooleanSupplier):820:822 -> a
+    13:13:void tickChildren(java.util.function.BooleanSupplier):900 -> a
+    java.lang.String getMotd() -> c
+net.minecraft.ReportedException -> xy:
+";
+
+    #[test]
+    fn test_parse_mappings() {
+        let mappings = Mappings::parse(MAPPINGS).unwrap();
+
+        assert_eq!(
+            mappings.deobfuscate_class("abc"),
+            Some("net.minecraft.server.MinecraftServer")
+        );
+        assert_eq!(
+            mappings.obfuscate_class("net.minecraft.ReportedException"),
+            Some("xy")
+        );
+
+        let class = mappings.class_by_obfuscated("abc").unwrap();
+        assert_eq!(class.field_by_obfuscated("b").unwrap().original, "motd");
+        assert_eq!(class.methods_by_obfuscated("a").count(), 2);
+
+        let tick = class.method_at("a", Some(11)).unwrap();
+        assert_eq!(tick.original, "tickServer");
+        assert_eq!(tick.arguments, ["java.util.function.BooleanSupplier"]);
+        assert_eq!(tick.original_line(11), 821);
+        assert_eq!(
+            class.method_at("a", Some(13)).unwrap().original_line(13),
+            900
+        );
+        assert_eq!(class.method_at("a", None), None);
+        assert_eq!(class.method_at("c", None).unwrap().original, "getMotd");
+
+        assert!(Mappings::parse("    int a -> b").is_err());
+
+        // Reversed line ranges are not translated
+        let reversed = Mappings::parse("abc -> d:\n    5:3:void run():9:7 -> a\n").unwrap();
+        let run = reversed.class_by_obfuscated("d").unwrap();
+        assert_eq!(run.method_at("a", None).unwrap().original_line(4), 9);
+    }
+
+    #[test]
+    fn test_remap_stack_trace() {
+        let mappings = Mappings::parse(MAPPINGS).unwrap();
+        let trace = "xy: Ticking entity\n\tat abc.a(SourceFile:11)\n\tat java.lang.Thread.run(Thread.java:840)\nCaused by: java.lang.NullPointerException\n\tat TRANSFORMER/minecraft@1.20.4/abc.c(SourceFile)\n";
+
+        assert_eq!(
+            mappings.remap_stack_trace(trace),
+            "net.minecraft.ReportedException: Ticking entity\n\tat net.minecraft.server.MinecraftServer.tickServer(SourceFile:821)\n\tat java.lang.Thread.run(Thread.java:840)\nCaused by: java.lang.NullPointerException\n\tat TRANSFORMER/minecraft@1.20.4/net.minecraft.server.MinecraftServer.getMotd(SourceFile)\n"
+        );
+    }
+
+    #[test]
+    fn test_remap_log4j_frame() {
+        let mappings = Mappings::parse(MAPPINGS).unwrap();
+
+        assert_eq!(
+            mappings.remap_stack_trace("\tat abc.a(SourceFile:11) ~[server-1.20.4.jar:?]\n"),
+            "\tat net.minecraft.server.MinecraftServer.tickServer(SourceFile:821) ~[server-1.20.4.jar:?]\n"
+        );
+    }
+}
@@ -0,0 +1,1258 @@
+//! Inspecting the mods and plugins of modded servers
+//!
+//! The metadata of a jar is read from `fabric.mod.json`, `quilt.mod.json`,
+//! `META-INF/neoforge.mods.toml`, `META-INF/mods.toml`, `paper-plugin.yml` or `plugin.yml`.
+//! Mods nested in a jar (jar-in-jar) are read as well.
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Cursor, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use zip::ZipArchive;
+
+pub const MODS_DIR: &str = "mods";
+pub const PLUGINS_DIR: &str = "plugins";
+
+/// The directories of nested jars, used by fabric and quilt and by forge's jarjar
+const NESTED_JAR_DIRS: &[&str] = &["META-INF/jars/", "META-INF/jarjar/"];
+
+/// How deep nested jars are read
+const MAX_NESTING: usize = 4;
+
+/// The placeholder forge replaces with the `Implementation-Version` of the jar manifest
+const JAR_VERSION_PLACEHOLDER: &str = "${file.jarVersion}";
+
+/// Ids which are provided by the server itself instead of a mod
+const PLATFORM_IDS: &[&str] = &[
+    "minecraft",
+    "java",
+    "fabricloader",
+    "quilt_loader",
+    "forge",
+    "neoforge",
+];
+
+/// The file a mod or plugin declared its metadata in
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ModFormat {
+    Fabric,
+    Quilt,
+    /// Forge and neoforge mods
+    Forge,
+    Bukkit,
+    Paper,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DependencyKind {
+    Required,
+    Optional,
+    /// The mods must not be installed together
+    Incompatible,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub id: String,
+    /// The accepted versions in the syntax of the mod loader, if restricted
+    pub version_range: Option<String>,
+    pub kind: DependencyKind,
+}
+
+/// The metadata of a mod or plugin jar
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModMetadata {
+    pub path: PathBuf,
+    pub format: ModFormat,
+    pub id: String,
+    pub version: String,
+    pub dependencies: Vec<Dependency>,
+    /// The minecraft versions declared as supported, in the syntax of the mod loader
+    pub minecraft_version: Option<String>,
+    /// Additional ids this mod can stand in for, declared by fabric and quilt mods
+    pub provides: Vec<String>,
+    /// The mods nested in this jar, which the loader loads as well
+    ///
+    /// Their paths are the path of this jar joined with their path inside of it.
+    pub bundled: Vec<ModMetadata>,
+}
+
+impl ModMetadata {
+    /// All mods nested in this jar, including the ones nested in nested jars
+    pub fn all_bundled(&self) -> Vec<&ModMetadata> {
+        self.bundled
+            .iter()
+            .flat_map(|bundled| Some(bundled).into_iter().chain(bundled.all_bundled()))
+            .collect()
+    }
+}
+
+/// The mods of a directory, see [`list_mods`]
+#[derive(Debug, Default)]
+pub struct ModList {
+    pub mods: Vec<ModMetadata>,
+    /// The jars whose metadata could not be read
+    pub errors: Vec<(PathBuf, io::Error)>,
+}
+
+/// The versions of the server itself, which mods can depend on
+///
+/// Dependencies on platform ids without a known version, like `minecraft` or `fabricloader`,
+/// are always satisfied.
+#[derive(Debug, Clone, Default)]
+pub struct Platform {
+    versions: HashMap<String, String>,
+}
+
+impl Platform {
+    pub fn new() -> Self {
+        Platform::default()
+    }
+
+    /// Sets the version of the platform id 'id', e.g. `minecraft` or `fabricloader`
+    pub fn version(mut self, id: impl Into<String>, version: impl Into<String>) -> Self {
+        self.versions.insert(id.into(), version.into());
+        self
+    }
+}
+
+/// A problem found by [`check_dependencies`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DependencyProblem {
+    /// A required dependency is not installed
+    Missing { id: String, dependency: Dependency },
+    /// Two installed mods are incompatible with each other
+    Incompatible { id: String, other: String },
+    /// The same id is provided by multiple jars
+    Duplicate { id: String, paths: Vec<PathBuf> },
+    /// A dependency is installed, but its version is not in the accepted range
+    WrongVersion {
+        id: String,
+        dependency: Dependency,
+        found: String,
+    },
+    /// The metadata of a jar could not be read
+    InvalidJar { path: PathBuf, error: String },
+}
+
+/// Reads the metadata of the mod or plugin jar at 'path'
+///
+/// Returns `None` if the jar contains no known metadata file.
+pub fn read_mod_metadata(path: impl AsRef<Path>) -> io::Result<Option<ModMetadata>> {
+    let path = path.as_ref();
+    let mut archive = ZipArchive::new(File::open(path)?).map_err(invalid_data)?;
+    read_archive_metadata(&mut archive, path, 0)
+}
+
+/// Reads the metadata of the jar 'archive' at 'path' and of the jars nested in it
+fn read_archive_metadata<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    path: &Path,
+    depth: usize,
+) -> io::Result<Option<ModMetadata>> {
+    let parsers: [(&str, MetadataParser); 6] = [
+        ("fabric.mod.json", parse_fabric),
+        ("quilt.mod.json", parse_quilt),
+        ("META-INF/neoforge.mods.toml", parse_forge),
+        ("META-INF/mods.toml", parse_forge),
+        ("paper-plugin.yml", parse_paper),
+        ("plugin.yml", parse_bukkit),
+    ];
+
+    for (file_name, parser) in parsers.iter() {
+        let mut file = match archive.by_name(file_name) {
+            Ok(file) => file,
+            Err(zip::result::ZipError::FileNotFound) => continue,
+            Err(err) => return Err(invalid_data(err)),
+        };
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+
+        drop(file);
+
+        let mut parsed = parser(&content)?;
+        if parsed.version == JAR_VERSION_PLACEHOLDER {
+            if let Some(version) = read_implementation_version(archive)? {
+                parsed.version = version;
+            }
+        }
+        let bundled = if depth < MAX_NESTING {
+            read_nested_jars(archive, path, &parsed.jars, depth + 1)?
+        } else {
+            Vec::new()
+        };
+        return Ok(Some(ModMetadata {
+            path: path.to_path_buf(),
+            format: parsed.format,
+            id: parsed.id,
+            version: parsed.version,
+            dependencies: parsed.dependencies,
+            minecraft_version: parsed.minecraft_version,
+            provides: parsed.provides,
+            bundled,
+        }));
+    }
+
+    Ok(None)
+}
+
+/// Reads the metadata of the jars in 'declared' and in the [`NESTED_JAR_DIRS`] of 'archive'
+///
+/// Nested jars without metadata, like plain libraries, are skipped.
+fn read_nested_jars<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    path: &Path,
+    declared: &[String],
+    depth: usize,
+) -> io::Result<Vec<ModMetadata>> {
+    let mut names: Vec<String> = archive
+        .file_names()
+        .filter(|name| {
+            name.ends_with(".jar") && NESTED_JAR_DIRS.iter().any(|dir| name.starts_with(dir))
+        })
+        .chain(declared.iter().map(String::as_str))
+        .map(String::from)
+        .collect();
+    names.sort_unstable();
+    names.dedup();
+
+    let mut bundled = Vec::new();
+    for name in names {
+        let mut content = Vec::new();
+        match archive.by_name(&name) {
+            Ok(mut file) => file.read_to_end(&mut content)?,
+            Err(zip::result::ZipError::FileNotFound) => continue,
+            Err(err) => return Err(invalid_data(err)),
+        };
+        let mut nested = ZipArchive::new(Cursor::new(content)).map_err(invalid_data)?;
+        bundled.extend(read_archive_metadata(
+            &mut nested,
+            &path.join(&name),
+            depth,
+        )?);
+    }
+    Ok(bundled)
+}
+
+/// Reads the `Implementation-Version` from the manifest of a jar
+fn read_implementation_version<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> io::Result<Option<String>> {
+    let mut manifest = String::new();
+    match archive.by_name("META-INF/MANIFEST.MF") {
+        Ok(mut file) => file.read_to_string(&mut manifest)?,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(invalid_data(err)),
+    };
+
+    let version = manifest
+        .lines()
+        .find_map(|line| line.strip_prefix("Implementation-Version:"))
+        .map(|version| version.trim().to_string());
+    Ok(version)
+}
+
+/// Lists the metadata of all jars in 'dir', usually the `mods` or `plugins` directory of a server
+///
+/// Jars without known metadata are skipped, jars which cannot be read are collected in
+/// [`ModList::errors`].
+pub fn list_mods(dir: impl AsRef<Path>) -> io::Result<ModList> {
+    let dir = dir.as_ref();
+    let mut list = ModList::default();
+    if !dir.exists() {
+        return Ok(list);
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "jar") {
+            match read_mod_metadata(&path) {
+                Ok(metadata) => list.mods.extend(metadata),
+                Err(error) => list.errors.push((path, error)),
+            }
+        }
+    }
+    list.mods.sort_unstable_by(|a, b| a.id.cmp(&b.id));
+    Ok(list)
+}
+
+/// Lists the mods and plugins of the server directory 'server_dir' and checks their dependencies
+///
+/// Jars which cannot be read are reported as [`DependencyProblem::InvalidJar`].
+pub fn check_server_mods(
+    server_dir: impl AsRef<Path>,
+    platform: &Platform,
+) -> io::Result<Vec<DependencyProblem>> {
+    let server_dir = server_dir.as_ref();
+    let mut list = list_mods(server_dir.join(MODS_DIR))?;
+    let plugins = list_mods(server_dir.join(PLUGINS_DIR))?;
+    list.mods.extend(plugins.mods);
+    list.errors.extend(plugins.errors);
+
+    let mut problems = check_dependencies(&list.mods, platform);
+    problems.extend(
+        list.errors
+            .into_iter()
+            .map(|(path, error)| DependencyProblem::InvalidJar {
+                path,
+                error: error.to_string(),
+            }),
+    );
+    Ok(problems)
+}
+
+/// Finds missing, conflicting and outdated dependencies
+///
+/// Version ranges are checked for fabric, quilt and forge mods. Ranges which cannot be
+/// parsed are accepted. Bundled mods satisfy dependencies, the newest bundled version of
+/// an id is used unless a top level mod provides it.
+pub fn check_dependencies(mods: &[ModMetadata], platform: &Platform) -> Vec<DependencyProblem> {
+    let mut by_id: HashMap<&str, Vec<&ModMetadata>> = HashMap::new();
+    for metadata in mods {
+        by_id.entry(&metadata.id).or_default().push(metadata);
+    }
+
+    let mut problems: Vec<DependencyProblem> = by_id
+        .iter()
+        .filter(|(_, mods)| mods.len() > 1)
+        .map(|(id, mods)| DependencyProblem::Duplicate {
+            id: id.to_string(),
+            paths: mods.iter().map(|metadata| metadata.path.clone()).collect(),
+        })
+        .collect();
+
+    // The installed version of every id, including the provided ones and the platform
+    let mut versions: HashMap<&str, &str> = HashMap::new();
+    for bundled in mods.iter().flat_map(ModMetadata::all_bundled) {
+        for id in bundled.provides.iter().chain(Some(&bundled.id)) {
+            let is_newer = versions.get(id.as_str()).is_none_or(|version| {
+                compare_versions(&bundled.version, version) == Ordering::Greater
+            });
+            if is_newer {
+                versions.insert(id, &bundled.version);
+            }
+        }
+    }
+    for metadata in mods {
+        for id in metadata.provides.iter().chain(Some(&metadata.id)) {
+            versions.insert(id, &metadata.version);
+        }
+    }
+    for (id, version) in &platform.versions {
+        versions.insert(id, version);
+    }
+
+    for metadata in mods {
+        for dependency in &metadata.dependencies {
+            let installed = versions.get(dependency.id.as_str());
+            let is_platform = PLATFORM_IDS.contains(&dependency.id.as_str());
+            match (dependency.kind, installed) {
+                (DependencyKind::Required, None) if !is_platform => {
+                    problems.push(DependencyProblem::Missing {
+                        id: metadata.id.clone(),
+                        dependency: dependency.clone(),
+                    })
+                }
+                (DependencyKind::Incompatible, Some(version))
+                    if dependency_matches(metadata.format, dependency, version) =>
+                {
+                    problems.push(DependencyProblem::Incompatible {
+                        id: metadata.id.clone(),
+                        other: dependency.id.clone(),
+                    })
+                }
+                (DependencyKind::Required, Some(version))
+                | (DependencyKind::Optional, Some(version))
+                    if !dependency_matches(metadata.format, dependency, version) =>
+                {
+                    problems.push(DependencyProblem::WrongVersion {
+                        id: metadata.id.clone(),
+                        dependency: dependency.clone(),
+                        found: version.to_string(),
+                    })
+                }
+                _ => {}
+            }
+        }
+    }
+
+    problems
+}
+
+/// Returns whether 'version' is in the range of 'dependency', declared by a mod of 'format'
+fn dependency_matches(format: ModFormat, dependency: &Dependency, version: &str) -> bool {
+    let range = match &dependency.version_range {
+        Some(range) => range,
+        None => return true,
+    };
+    let matches = match format {
+        ModFormat::Fabric | ModFormat::Quilt => matches_fabric_range(version, range),
+        ModFormat::Forge => matches_maven_range(version, range),
+        ModFormat::Bukkit | ModFormat::Paper => None,
+    };
+    matches.unwrap_or(true)
+}
+
+/// Checks a fabric version range like `>=1.2 <2`, `~1.20.4`, `1.20.x` or `1.19 || 1.20`
+///
+/// Returns None if the range cannot be parsed.
+fn matches_fabric_range(version: &str, range: &str) -> Option<bool> {
+    let mut any = false;
+    for alternative in range.split("||") {
+        let mut all = true;
+        for predicate in alternative.split_whitespace() {
+            all &= matches_fabric_predicate(version, predicate)?;
+        }
+        any |= all;
+    }
+    Some(any)
+}
+
+fn matches_fabric_predicate(version: &str, predicate: &str) -> Option<bool> {
+    if predicate == "*" {
+        return Some(true);
+    }
+
+    let operator_len = predicate
+        .find(|c| !['<', '>', '='].contains(&c))
+        .unwrap_or(predicate.len());
+    let (operator, bound) = predicate.split_at(operator_len);
+    let ordering = compare_versions(version, bound);
+    match operator {
+        "" => (),
+        ">=" => return Some(ordering != Ordering::Less),
+        "<=" => return Some(ordering != Ordering::Greater),
+        ">" => return Some(ordering == Ordering::Greater),
+        "<" => return Some(ordering == Ordering::Less),
+        "=" => return Some(ordering == Ordering::Equal),
+        _ => return None,
+    }
+
+    if let Some(bound) = predicate.strip_prefix('~') {
+        // Allows patch updates, e.g. `~1.20.4` is `>=1.20.4 <1.21`
+        let upper = bump_version(bound, 1.min(count_components(bound) - 1))?;
+        return Some(
+            compare_versions(version, bound) != Ordering::Less
+                && compare_versions(version, &upper) == Ordering::Less,
+        );
+    }
+    if let Some(bound) = predicate.strip_prefix('^') {
+        // Allows updates which keep the first non-zero component
+        let components = numeric_components(bound)?;
+        let index = components
+            .iter()
+            .position(|&component| component != 0)
+            .unwrap_or(components.len() - 1);
+        let upper = bump_version(bound, index)?;
+        return Some(
+            compare_versions(version, bound) != Ordering::Less
+                && compare_versions(version, &upper) == Ordering::Less,
+        );
+    }
+
+    if let Some(prefix) = predicate
+        .strip_suffix(".x")
+        .or_else(|| predicate.strip_suffix(".X"))
+        .or_else(|| predicate.strip_suffix(".*"))
+    {
+        let version_core = core_version(version);
+        let mut version_parts = version_core.split('.');
+        let matches = prefix.split('.').all(|part| {
+            version_parts
+                .next()
+                .is_some_and(|version_part| version_part == part)
+        });
+        return Some(matches);
+    }
+
+    Some(compare_versions(version, predicate) == Ordering::Equal)
+}
+
+/// Checks a maven version range like `[1.20.4,1.21)`, `[49,)` or `[1,2),[3,4)`
+///
+/// A plain version like `1.0` is only a recommendation and accepts every version.
+/// Returns None if the range cannot be parsed.
+fn matches_maven_range(version: &str, range: &str) -> Option<bool> {
+    let mut rest = range.trim();
+    if !rest.starts_with('[') && !rest.starts_with('(') {
+        return Some(true);
+    }
+
+    let mut any = false;
+    while !rest.is_empty() {
+        let end = rest.find([']', ')'])?;
+        let (lower_inclusive, upper_inclusive) = (rest.starts_with('['), &rest[end..=end] == "]");
+        let bounds = rest.get(1..end)?;
+        rest = rest[end + 1..]
+            .trim_start()
+            .trim_start_matches(',')
+            .trim_start();
+
+        any |= match bounds.split_once(',') {
+            None => compare_versions(version, bounds.trim()) == Ordering::Equal,
+            Some((lower, upper)) => {
+                let (lower, upper) = (lower.trim(), upper.trim());
+                let above_lower = lower.is_empty()
+                    || match compare_versions(version, lower) {
+                        Ordering::Greater => true,
+                        Ordering::Equal => lower_inclusive,
+                        Ordering::Less => false,
+                    };
+                let below_upper = upper.is_empty()
+                    || match compare_versions(version, upper) {
+                        Ordering::Less => true,
+                        Ordering::Equal => upper_inclusive,
+                        Ordering::Greater => false,
+                    };
+                above_lower && below_upper
+            }
+        };
+    }
+    Some(any)
+}
+
+/// Compares versions like `1.20.4` or `2.0.0-beta.1` component by component
+///
+/// Missing components count as zero and pre-releases sort before their release.
+/// Build metadata after a `+` is ignored.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (a_core, a_pre) = split_pre_release(a);
+    let (b_core, b_pre) = split_pre_release(b);
+
+    compare_components(a_core, b_core).then_with(|| match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a_pre), Some(b_pre)) => compare_components(a_pre, b_pre),
+    })
+}
+
+fn compare_components(a: &str, b: &str) -> Ordering {
+    let mut a_parts = a.split('.');
+    let mut b_parts = b.split('.');
+    loop {
+        let ordering = match (a_parts.next(), b_parts.next()) {
+            (None, None) => return Ordering::Equal,
+            (a, b) => {
+                let (a, b) = (a.unwrap_or("0"), b.unwrap_or("0"));
+                match (a.parse::<u64>(), b.parse::<u64>()) {
+                    (Ok(a), Ok(b)) => a.cmp(&b),
+                    // Numeric identifiers sort before alphanumeric ones
+                    (Ok(_), Err(_)) => Ordering::Less,
+                    (Err(_), Ok(_)) => Ordering::Greater,
+                    (Err(_), Err(_)) => a.cmp(b),
+                }
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+/// The version without pre-release and build metadata
+fn core_version(version: &str) -> &str {
+    split_pre_release(version).0
+}
+
+fn split_pre_release(version: &str) -> (&str, Option<&str>) {
+    let version = version.split('+').next().unwrap_or(version);
+    match version.split_once('-') {
+        Some((core, pre)) => (core, Some(pre)),
+        None => (version, None),
+    }
+}
+
+fn numeric_components(version: &str) -> Option<Vec<u64>> {
+    core_version(version)
+        .split('.')
+        .map(|component| component.parse().ok())
+        .collect()
+}
+
+fn count_components(version: &str) -> usize {
+    core_version(version).split('.').count()
+}
+
+/// Increments the component at 'index' and drops all later components, e.g. `1.20.4` to `1.21`
+fn bump_version(version: &str, index: usize) -> Option<String> {
+    let mut components = numeric_components(version)?;
+    components.truncate(index + 1);
+    *components.get_mut(index)? += 1;
+    let components: Vec<_> = components.iter().map(u64::to_string).collect();
+    Some(components.join("."))
+}
+
+type MetadataParser = fn(&str) -> io::Result<ParsedMetadata>;
+
+struct ParsedMetadata {
+    format: ModFormat,
+    id: String,
+    version: String,
+    dependencies: Vec<Dependency>,
+    minecraft_version: Option<String>,
+    provides: Vec<String>,
+    /// The nested jars declared in the metadata file
+    jars: Vec<String>,
+}
+
+impl ParsedMetadata {
+    fn new(format: ModFormat, id: String, version: String, dependencies: Vec<Dependency>) -> Self {
+        let minecraft_version = dependencies
+            .iter()
+            .find(|dependency| dependency.id == "minecraft")
+            .and_then(|dependency| dependency.version_range.clone());
+        ParsedMetadata {
+            format,
+            id,
+            version,
+            dependencies,
+            minecraft_version,
+            provides: Vec::new(),
+            jars: Vec::new(),
+        }
+    }
+}
+
+/// A version requirement of fabric, which is either a single string or a list of alternatives
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum FabricVersions {
+    Single(String),
+    Any(Vec<String>),
+}
+
+impl FabricVersions {
+    fn into_range(self) -> Option<String> {
+        let range = match self {
+            FabricVersions::Single(range) => range,
+            FabricVersions::Any(ranges) => ranges.join(" || "),
+        };
+        Some(range).filter(|range| range != "*")
+    }
+}
+
+fn parse_fabric(content: &str) -> io::Result<ParsedMetadata> {
+    #[derive(Deserialize)]
+    struct FabricMod {
+        id: String,
+        version: String,
+        #[serde(default)]
+        depends: HashMap<String, FabricVersions>,
+        #[serde(default)]
+        recommends: HashMap<String, FabricVersions>,
+        #[serde(default)]
+        breaks: HashMap<String, FabricVersions>,
+        #[serde(default)]
+        provides: Vec<String>,
+        #[serde(default)]
+        jars: Vec<FabricJar>,
+    }
+
+    #[derive(Deserialize)]
+    struct FabricJar {
+        file: String,
+    }
+
+    let fabric_mod: FabricMod = serde_json::from_str(content).map_err(invalid_data)?;
+    let dependencies = vec![
+        (fabric_mod.depends, DependencyKind::Required),
+        (fabric_mod.recommends, DependencyKind::Optional),
+        (fabric_mod.breaks, DependencyKind::Incompatible),
+    ]
+    .into_iter()
+    .flat_map(|(dependencies, kind)| {
+        dependencies
+            .into_iter()
+            .map(move |(id, versions)| Dependency {
+                id,
+                version_range: versions.into_range(),
+                kind,
+            })
+    })
+    .collect();
+
+    let mut parsed = ParsedMetadata::new(
+        ModFormat::Fabric,
+        fabric_mod.id,
+        fabric_mod.version,
+        dependencies,
+    );
+    parsed.provides = fabric_mod.provides;
+    parsed.jars = fabric_mod.jars.into_iter().map(|jar| jar.file).collect();
+    Ok(parsed)
+}
+
+fn parse_quilt(content: &str) -> io::Result<ParsedMetadata> {
+    #[derive(Deserialize)]
+    struct QuiltMod {
+        quilt_loader: QuiltLoader,
+    }
+
+    #[derive(Deserialize)]
+    struct QuiltLoader {
+        id: String,
+        version: String,
+        #[serde(default)]
+        depends: Vec<QuiltDependency>,
+        #[serde(default)]
+        breaks: Vec<QuiltDependency>,
+        #[serde(default)]
+        provides: Vec<QuiltProvided>,
+        #[serde(default)]
+        jars: Vec<String>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum QuiltProvided {
+        Id(String),
+        Object { id: String },
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum QuiltDependency {
+        Id(String),
+        Object {
+            id: String,
+            versions: Option<FabricVersions>,
+            #[serde(default)]
+            optional: bool,
+        },
+    }
+
+    impl QuiltDependency {
+        fn into_dependency(self, incompatible: bool) -> Dependency {
+            let (id, version_range, optional) = match self {
+                QuiltDependency::Id(id) => (id, None, false),
+                QuiltDependency::Object {
+                    id,
+                    versions,
+                    optional,
+                } => (id, versions.and_then(FabricVersions::into_range), optional),
+            };
+            let kind = match (incompatible, optional) {
+                (true, _) => DependencyKind::Incompatible,
+                (false, true) => DependencyKind::Optional,
+                (false, false) => DependencyKind::Required,
+            };
+            Dependency {
+                id,
+                version_range,
+                kind,
+            }
+        }
+    }
+
+    let loader = serde_json::from_str::<QuiltMod>(content)
+        .map_err(invalid_data)?
+        .quilt_loader;
+    let dependencies = loader
+        .depends
+        .into_iter()
+        .map(|dependency| dependency.into_dependency(false))
+        .chain(
+            loader
+                .breaks
+                .into_iter()
+                .map(|dependency| dependency.into_dependency(true)),
+        )
+        .collect();
+
+    let mut parsed = ParsedMetadata::new(ModFormat::Quilt, loader.id, loader.version, dependencies);
+    parsed.provides = loader
+        .provides
+        .into_iter()
+        .map(|provided| match provided {
+            QuiltProvided::Id(id) | QuiltProvided::Object { id } => id,
+        })
+        .collect();
+    parsed.jars = loader.jars;
+    Ok(parsed)
+}
+
+fn parse_forge(content: &str) -> io::Result<ParsedMetadata> {
+    #[derive(Deserialize)]
+    struct ModsToml {
+        mods: Vec<ForgeMod>,
+        #[serde(default)]
+        dependencies: HashMap<String, Vec<ForgeDependency>>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ForgeMod {
+        mod_id: String,
+        #[serde(default)]
+        version: String,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ForgeDependency {
+        mod_id: String,
+        /// Used by forge
+        mandatory: Option<bool>,
+        /// Used by neoforge, one of `required`, `optional`, `incompatible` or `discouraged`
+        #[serde(rename = "type")]
+        typ: Option<String>,
+        version_range: Option<String>,
+    }
+
+    let mut mods_toml: ModsToml = toml::from_str(content).map_err(invalid_data)?;
+    let main_mod = mods_toml
+        .mods
+        .drain(..)
+        .next()
+        .ok_or_else(|| invalid_data("mods.toml does not declare any mods"))?;
+    let dependencies = mods_toml
+        .dependencies
+        .remove(&main_mod.mod_id)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|dependency| {
+            let kind = match (dependency.typ.as_deref(), dependency.mandatory) {
+                (Some("required"), _) | (None, Some(true)) | (None, None) => {
+                    DependencyKind::Required
+                }
+                (Some("incompatible"), _) => DependencyKind::Incompatible,
+                _ => DependencyKind::Optional,
+            };
+            Dependency {
+                id: dependency.mod_id,
+                version_range: dependency.version_range,
+                kind,
+            }
+        })
+        .collect();
+
+    Ok(ParsedMetadata::new(
+        ModFormat::Forge,
+        main_mod.mod_id,
+        main_mod.version,
+        dependencies,
+    ))
+}
+
+fn parse_bukkit(content: &str) -> io::Result<ParsedMetadata> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct PluginYml {
+        name: String,
+        version: serde_yaml::Value,
+        api_version: Option<serde_yaml::Value>,
+        #[serde(default)]
+        depend: Vec<String>,
+        #[serde(default)]
+        softdepend: Vec<String>,
+    }
+
+    let plugin: PluginYml = serde_yaml::from_str(content).map_err(invalid_data)?;
+    let dependencies = plugin
+        .depend
+        .into_iter()
+        .map(|id| (id, DependencyKind::Required))
+        .chain(
+            plugin
+                .softdepend
+                .into_iter()
+                .map(|id| (id, DependencyKind::Optional)),
+        )
+        .map(|(id, kind)| Dependency {
+            id,
+            version_range: None,
+            kind,
+        })
+        .collect();
+
+    let mut parsed = ParsedMetadata::new(
+        ModFormat::Bukkit,
+        plugin.name,
+        yaml_to_string(plugin.version),
+        dependencies,
+    );
+    parsed.minecraft_version = plugin.api_version.map(yaml_to_string);
+    Ok(parsed)
+}
+
+fn parse_paper(content: &str) -> io::Result<ParsedMetadata> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct PaperPluginYml {
+        name: String,
+        version: serde_yaml::Value,
+        api_version: Option<serde_yaml::Value>,
+        #[serde(default)]
+        dependencies: PaperDependencies,
+    }
+
+    #[derive(Deserialize, Default)]
+    struct PaperDependencies {
+        #[serde(default)]
+        server: HashMap<String, PaperDependency>,
+    }
+
+    #[derive(Deserialize)]
+    struct PaperDependency {
+        #[serde(default = "default_required")]
+        required: bool,
+    }
+
+    fn default_required() -> bool {
+        true
+    }
+
+    let plugin: PaperPluginYml = serde_yaml::from_str(content).map_err(invalid_data)?;
+    let dependencies = plugin
+        .dependencies
+        .server
+        .into_iter()
+        .map(|(id, dependency)| Dependency {
+            id,
+            version_range: None,
+            kind: if dependency.required {
+                DependencyKind::Required
+            } else {
+                DependencyKind::Optional
+            },
+        })
+        .collect();
+
+    let mut parsed = ParsedMetadata::new(
+        ModFormat::Paper,
+        plugin.name,
+        yaml_to_string(plugin.version),
+        dependencies,
+    );
+    parsed.minecraft_version = plugin.api_version.map(yaml_to_string);
+    Ok(parsed)
+}
+
+/// Versions in yaml files are often written as numbers, like `version: 1.0`
+fn yaml_to_string(value: serde_yaml::Value) -> String {
+    match value {
+        serde_yaml::Value::String(string) => string,
+        serde_yaml::Value::Number(number) => number.to_string(),
+        other => serde_yaml::to_string(&other)
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+    }
+}
+
+fn invalid_data(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs::{self, File},
+        io::{Cursor, Write},
+        path::Path,
+    };
+
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use super::{
+        check_dependencies, check_server_mods, compare_versions, list_mods, matches_fabric_range,
+        matches_maven_range, read_mod_metadata, DependencyKind, DependencyProblem, ModFormat,
+        Platform,
+    };
+
+    fn write_jar(path: &Path, file_name: &str, content: &str) {
+        write_jar_files(path, &[(file_name, content)]);
+    }
+
+    fn write_jar_files(path: &Path, files: &[(&str, &str)]) {
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        for (file_name, content) in files {
+            zip.start_file(*file_name, SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn fabric_mod(dir: &Path, id: &str, version: &str, extra: &str) {
+        write_jar(
+            &dir.join(format!("{}.jar", id)),
+            "fabric.mod.json",
+            &format!(
+                r#"{{"schemaVersion": 1, "id": "{}", "version": "{}"{}}}"#,
+                id, version, extra
+            ),
+        );
+    }
+
+    #[test]
+    fn test_read_fabric_mod() {
+        let dir = tempfile::tempdir().unwrap();
+        let jar = dir.path().join("example.jar");
+        write_jar(
+            &jar,
+            "fabric.mod.json",
+            r#"{"schemaVersion": 1, "id": "example", "version": "1.0.0",
+                "depends": {"minecraft": "~1.20.4", "fabric-api": "*"},
+                "breaks": {"optifabric": "*"}}"#,
+        );
+
+        let metadata = read_mod_metadata(&jar).unwrap().unwrap();
+        assert_eq!(metadata.format, ModFormat::Fabric);
+        assert_eq!(metadata.id, "example");
+        assert_eq!(metadata.minecraft_version.as_deref(), Some("~1.20.4"));
+        assert_eq!(metadata.dependencies.len(), 3);
+    }
+
+    #[test]
+    fn test_read_forge_mod() {
+        let dir = tempfile::tempdir().unwrap();
+        let jar = dir.path().join("example.jar");
+        write_jar(
+            &jar,
+            "META-INF/mods.toml",
+            r#"
+modLoader = "javafml"
+loaderVersion = "[49,)"
+
+[[mods]]
+modId = "example"
+version = "2.1.0"
+
+[[dependencies.example]]
+modId = "minecraft"
+mandatory = true
+versionRange = "[1.20.4,1.21)"
+
+[[dependencies.example]]
+modId = "jei"
+mandatory = false
+"#,
+        );
+
+        let metadata = read_mod_metadata(&jar).unwrap().unwrap();
+        assert_eq!(metadata.format, ModFormat::Forge);
+        assert_eq!(metadata.version, "2.1.0");
+        assert_eq!(metadata.minecraft_version.as_deref(), Some("[1.20.4,1.21)"));
+        assert_eq!(metadata.dependencies[1].kind, DependencyKind::Optional);
+    }
+
+    #[test]
+    fn test_check_plugin_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        write_jar(
+            &dir.path().join("shop.jar"),
+            "plugin.yml",
+            "name: Shop\nversion: 1.2\napi-version: '1.20'\ndepend: [Vault]\nsoftdepend: [Essentials]\n",
+        );
+        write_jar(
+            &dir.path().join("other.jar"),
+            "paper-plugin.yml",
+            "name: Other\nversion: '3.0'\ndependencies:\n  server:\n    Shop:\n      required: true\n",
+        );
+
+        let plugins = list_mods(dir.path()).unwrap().mods;
+        assert_eq!(plugins.len(), 2);
+        assert_eq!(plugins[1].version, "1.2");
+        assert_eq!(plugins[1].minecraft_version.as_deref(), Some("1.20"));
+
+        let problems = check_dependencies(&plugins, &Platform::new());
+        assert_eq!(problems.len(), 1);
+        assert!(matches!(
+            &problems[0],
+            DependencyProblem::Missing { id, dependency } if id == "Shop" && dependency.id == "Vault"
+        ));
+    }
+
+    /// Writes a jar with the files 'files' into memory
+    fn jar_bytes(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (file_name, content) in files {
+            zip.start_file(*file_name, SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_nested_jars() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = jar_bytes(&[(
+            "fabric.mod.json",
+            br#"{"schemaVersion": 1, "id": "fabric-api-base", "version": "0.4.36"}"#,
+        )]);
+        // Nested jars can contain nested jars themselves
+        let networking = jar_bytes(&[
+            (
+                "fabric.mod.json",
+                br#"{"schemaVersion": 1, "id": "fabric-networking-api-v1", "version": "3.1.0", "provides": ["fabric-networking-v0"]}"#,
+            ),
+            ("META-INF/jars/fabric-api-base.jar", &base),
+        ]);
+        let library = jar_bytes(&[(
+            "quilt.mod.json",
+            br#"{"quilt_loader": {"id": "library", "version": "2.0"}}"#,
+        )]);
+        fs::write(
+            dir.path().join("fabric-api.jar"),
+            jar_bytes(&[
+                (
+                    "fabric.mod.json",
+                    br#"{"schemaVersion": 1, "id": "fabric-api", "version": "0.95.4", "jars": [{"file": "META-INF/jars/networking.jar"}, {"file": "libs/library.jar"}]}"#,
+                ),
+                ("META-INF/jars/networking.jar", &networking),
+                ("libs/library.jar", &library),
+                ("META-INF/jars/plain-library.jar", &jar_bytes(&[])),
+            ]),
+        )
+        .unwrap();
+        fabric_mod(
+            dir.path(),
+            "sodium",
+            "0.5.8",
+            r#", "depends": {"fabric-api-base": ">=0.4", "fabric-networking-v0": "*", "library": "^2"}"#,
+        );
+
+        let mods = list_mods(dir.path()).unwrap().mods;
+        let fabric_api = &mods[0];
+        let bundled: Vec<_> = fabric_api
+            .all_bundled()
+            .iter()
+            .map(|bundled| bundled.id.as_str())
+            .collect();
+        assert_eq!(
+            bundled,
+            ["fabric-networking-api-v1", "fabric-api-base", "library"]
+        );
+        assert_eq!(
+            fabric_api.bundled[1].path,
+            dir.path().join("fabric-api.jar/libs/library.jar")
+        );
+
+        assert_eq!(check_dependencies(&mods, &Platform::new()), []);
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert!(compare_versions("1.20.4", "1.20.10").is_lt());
+        assert!(compare_versions("1.20", "1.20.0").is_eq());
+        assert!(compare_versions("1.0.0-beta.2", "1.0.0").is_lt());
+        assert!(compare_versions("1.0.0-beta.2", "1.0.0-beta.10").is_lt());
+        assert!(compare_versions("0.15.6+build.1", "0.15.6").is_eq());
+    }
+
+    #[test]
+    fn test_version_ranges() {
+        assert_eq!(matches_fabric_range("1.20.4", ">=1.20 <1.21"), Some(true));
+        assert_eq!(matches_fabric_range("1.21", ">=1.20 <1.21"), Some(false));
+        assert_eq!(matches_fabric_range("1.20.6", "~1.20.4"), Some(true));
+        assert_eq!(matches_fabric_range("1.21", "~1.20.4"), Some(false));
+        assert_eq!(matches_fabric_range("0.3.1", "^0.2.3"), Some(false));
+        assert_eq!(matches_fabric_range("1.9.0", "^1.2.3"), Some(true));
+        assert_eq!(matches_fabric_range("1.20.2", "1.20.x"), Some(true));
+        assert_eq!(
+            matches_fabric_range("1.19.2", "1.18.2 || 1.19.2"),
+            Some(true)
+        );
+        assert_eq!(matches_fabric_range("1.19.3", "1.19.2"), Some(false));
+
+        assert_eq!(matches_maven_range("1.20.4", "[1.20.4,1.21)"), Some(true));
+        assert_eq!(matches_maven_range("1.21", "[1.20.4,1.21)"), Some(false));
+        assert_eq!(matches_maven_range("49.0.3", "[49,)"), Some(true));
+        assert_eq!(matches_maven_range("3.5", "[1,2),[3,4)"), Some(true));
+        assert_eq!(matches_maven_range("2.5", "[1,2),[3,4)"), Some(false));
+        assert_eq!(matches_maven_range("1.1", "[1.0]"), Some(false));
+        assert_eq!(matches_maven_range("0.1", "1.0"), Some(true));
+        assert_eq!(matches_maven_range("1.0", "[1.0"), None);
+    }
+
+    #[test]
+    fn test_check_versions_and_provides() {
+        let dir = tempfile::tempdir().unwrap();
+        fabric_mod(
+            dir.path(),
+            "example",
+            "1.0.0",
+            r#", "depends": {"minecraft": "~1.20.4", "cloth": ">=2", "fabric-api": "*"}"#,
+        );
+        fabric_mod(
+            dir.path(),
+            "cloth-config",
+            "1.5.0",
+            r#", "provides": ["cloth"]"#,
+        );
+        fabric_mod(
+            dir.path(),
+            "fabric-api-base",
+            "0.92.0",
+            r#", "provides": ["fabric-api"]"#,
+        );
+
+        let mods = list_mods(dir.path()).unwrap().mods;
+        let platform = Platform::new().version("minecraft", "1.21");
+        let problems = check_dependencies(&mods, &platform);
+
+        assert_eq!(problems.len(), 2);
+        assert!(problems.iter().any(|problem| matches!(
+            problem,
+            DependencyProblem::WrongVersion { dependency, found, .. }
+                if dependency.id == "minecraft" && found == "1.21"
+        )));
+        assert!(problems.iter().any(|problem| matches!(
+            problem,
+            DependencyProblem::WrongVersion { dependency, found, .. }
+                if dependency.id == "cloth" && found == "1.5.0"
+        )));
+
+        let platform = Platform::new().version("minecraft", "1.20.4");
+        assert_eq!(check_dependencies(&mods, &platform).len(), 1);
+    }
+
+    #[test]
+    fn test_forge_jar_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let jar = dir.path().join("example.jar");
+        write_jar_files(
+            &jar,
+            &[
+                (
+                    "META-INF/MANIFEST.MF",
+                    "Manifest-Version: 1.0\r\nImplementation-Version: 2.1.0\r\n",
+                ),
+                (
+                    "META-INF/mods.toml",
+                    "[[mods]]\nmodId = \"example\"\nversion = \"${file.jarVersion}\"\n",
+                ),
+            ],
+        );
+
+        let metadata = read_mod_metadata(&jar).unwrap().unwrap();
+        assert_eq!(metadata.version, "2.1.0");
+    }
+
+    #[test]
+    fn test_invalid_jar() {
+        let dir = tempfile::tempdir().unwrap();
+        let mods = dir.path().join("mods");
+        fs::create_dir(&mods).unwrap();
+        fabric_mod(&mods, "example", "1.0.0", "");
+        fs::write(mods.join("broken.jar"), "not a zip").unwrap();
+
+        let list = list_mods(&mods).unwrap();
+        assert_eq!(list.mods.len(), 1);
+        assert_eq!(list.errors.len(), 1);
+        assert_eq!(list.errors[0].0, mods.join("broken.jar"));
+
+        let problems = check_server_mods(dir.path(), &Platform::new()).unwrap();
+        assert!(matches!(
+            &problems[..],
+            [DependencyProblem::InvalidJar { path, .. }] if path == &mods.join("broken.jar")
+        ));
+    }
+}
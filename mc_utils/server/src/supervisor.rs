@@ -0,0 +1,510 @@
+//! Keeps a server running by restarting it after crashes
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::instance::{InstanceError, Result, ServerBuilder, ServerInstance};
+
+pub const CRASH_REPORTS_DIR: &str = "crash-reports";
+
+/// How often the supervisor checks whether the server is still alive
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The lifecycle state of a supervised server
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ServerState {
+    Starting,
+    Running,
+    Stopping,
+    /// The server was stopped on request or exited cleanly
+    Stopped,
+    /// The server exited unexpectedly and is either waiting to be restarted
+    /// or exceeded the restart limit
+    Crashed,
+}
+
+/// Decides when and how often a crashed server gets restarted
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    /// The delay before the first restart, doubled after every consecutive crash
+    pub initial_backoff: Duration,
+    /// The upper bound for the delay between restarts
+    pub max_backoff: Duration,
+    /// A server which ran at least this long resets the delay to `initial_backoff`
+    pub stable_after: Duration,
+    /// The maximum number of restarts within `window`
+    pub max_restarts: usize,
+    pub window: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            stable_after: Duration::from_secs(60),
+            max_restarts: 5,
+            window: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+/// A crash report written by the server to `crash-reports/`
+#[derive(Debug, Clone)]
+pub struct CrashReport {
+    pub path: PathBuf,
+    pub content: String,
+}
+
+/// Returns the newest crash report in the server directory 'dir' which was written after 'since'
+pub fn latest_crash_report(
+    dir: impl AsRef<Path>,
+    since: SystemTime,
+) -> std::io::Result<Option<CrashReport>> {
+    let reports_dir = dir.as_ref().join(CRASH_REPORTS_DIR);
+    if !reports_dir.exists() {
+        return Ok(None);
+    }
+
+    let mut latest: Option<(SystemTime, PathBuf)> = None;
+    for entry in fs::read_dir(reports_dir)? {
+        let entry = entry?;
+        let modified = entry.metadata()?.modified()?;
+        let is_newer = latest.as_ref().is_none_or(|(time, _)| modified > *time);
+        if modified >= since && is_newer {
+            latest = Some((modified, entry.path()));
+        }
+    }
+
+    latest
+        .map(|(_, path)| {
+            let content = fs::read_to_string(&path)?;
+            Ok(CrashReport { path, content })
+        })
+        .transpose()
+}
+
+/// A process which can be watched by the supervisor
+trait Supervised {
+    fn try_wait(&mut self) -> Result<Option<ExitStatus>>;
+    fn try_stop(&mut self) -> Result<bool>;
+}
+
+impl Supervised for ServerInstance {
+    fn try_wait(&mut self) -> Result<Option<ExitStatus>> {
+        ServerInstance::try_wait(self)
+    }
+
+    fn try_stop(&mut self) -> Result<bool> {
+        ServerInstance::try_stop(self)
+    }
+}
+
+#[derive(Debug)]
+struct Shared<P = ServerInstance> {
+    state: ServerState,
+    instance: Option<P>,
+    crash_report: Option<CrashReport>,
+    restarts: usize,
+}
+
+/// Runs a server in a background thread and restarts it when it crashes
+///
+/// A server which exits cleanly, e.g. after `/stop`, is not restarted.
+/// The server is stopped when the supervisor is dropped.
+#[derive(Debug)]
+pub struct Supervisor {
+    shared: Arc<Mutex<Shared>>,
+    stop_requested: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Supervisor {
+    /// Starts the server described by 'builder' in the background
+    ///
+    /// The ports and the rcon password are chosen once, so every restart uses the same ones.
+    pub fn start(mut builder: ServerBuilder, policy: RestartPolicy) -> Result<Self> {
+        builder.resolve_properties()?;
+
+        let shared = Arc::new(Mutex::new(Shared {
+            state: ServerState::Starting,
+            instance: None,
+            crash_report: None,
+            restarts: 0,
+        }));
+        let stop_requested = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let shared = Arc::clone(&shared);
+            let stop_requested = Arc::clone(&stop_requested);
+            thread::spawn(move || {
+                supervise(
+                    || builder.clone().build(),
+                    builder.dir(),
+                    &policy,
+                    &shared,
+                    &stop_requested,
+                )
+            })
+        };
+
+        Ok(Supervisor {
+            shared,
+            stop_requested,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn state(&self) -> ServerState {
+        self.lock().state
+    }
+
+    /// The crash report of the last crash, if the server wrote one
+    pub fn crash_report(&self) -> Option<CrashReport> {
+        self.lock().crash_report.clone()
+    }
+
+    /// The total number of restarts after crashes
+    pub fn restarts(&self) -> usize {
+        self.lock().restarts
+    }
+
+    /// Executes a command at the server
+    ///
+    /// Returns Err if the server is not running right now.
+    pub fn command(&self, command: &str) -> Result<()> {
+        match self.lock().instance.as_mut() {
+            Some(instance) => instance.command(command),
+            None => Err(InstanceError::Other(
+                "The server is not running".to_string(),
+            )),
+        }
+    }
+
+    /// Stops the server and waits until it exited
+    pub fn stop(mut self) {
+        self.stop_and_join();
+    }
+
+    fn stop_and_join(&mut self) {
+        self.stop_requested.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Shared> {
+        self.shared.lock().expect("Supervisor thread panicked")
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        self.stop_and_join();
+    }
+}
+
+/// Starts processes with 'start' until one is stopped, exits cleanly or the restart limit is hit
+fn supervise<P: Supervised>(
+    mut start: impl FnMut() -> Result<P>,
+    dir: &Path,
+    policy: &RestartPolicy,
+    shared: &Mutex<Shared<P>>,
+    stop_requested: &AtomicBool,
+) {
+    let set_state = |state| shared.lock().unwrap().state = state;
+
+    let mut crashes = 0;
+    let mut restart_times: Vec<Instant> = Vec::new();
+
+    loop {
+        set_state(ServerState::Starting);
+        let started_at = SystemTime::now();
+        let started = Instant::now();
+
+        if let Ok(instance) = start() {
+            {
+                let mut shared = shared.lock().unwrap();
+                shared.instance = Some(instance);
+                shared.state = ServerState::Running;
+            }
+
+            let status = loop {
+                if stop_requested.load(Ordering::SeqCst) {
+                    break None;
+                }
+
+                let mut shared = shared.lock().unwrap();
+                let instance = shared.instance.as_mut().unwrap();
+                match instance.try_wait() {
+                    Ok(None) => (),
+                    Ok(Some(status)) => break Some(status.success()),
+                    Err(_) => break Some(false),
+                }
+                drop(shared);
+
+                thread::sleep(POLL_INTERVAL);
+            };
+
+            let instance = shared.lock().unwrap().instance.take();
+            match status {
+                None => {
+                    set_state(ServerState::Stopping);
+                    if let Some(mut instance) = instance {
+                        instance.try_stop().ok();
+                    }
+                    set_state(ServerState::Stopped);
+                    return;
+                }
+                // The server can write a crash report and still exit successfully
+                Some(true)
+                    if latest_crash_report(dir, started_at)
+                        .ok()
+                        .flatten()
+                        .is_none() =>
+                {
+                    set_state(ServerState::Stopped);
+                    return;
+                }
+                Some(_) => (),
+            }
+        }
+
+        // The server either crashed or could not be started
+        {
+            let mut shared = shared.lock().unwrap();
+            shared.state = ServerState::Crashed;
+            shared.crash_report = latest_crash_report(dir, started_at).ok().flatten();
+        }
+
+        let now = Instant::now();
+        restart_times.retain(|time| now.duration_since(*time) < policy.window);
+        if restart_times.len() >= policy.max_restarts {
+            return;
+        }
+
+        if now.duration_since(started) >= policy.stable_after {
+            crashes = 0;
+        }
+        let delay = backoff(crashes, policy);
+        crashes += 1;
+        if !sleep_unless_stopped(delay, stop_requested) {
+            set_state(ServerState::Stopped);
+            return;
+        }
+        restart_times.push(Instant::now());
+        shared.lock().unwrap().restarts += 1;
+    }
+}
+
+/// The delay before the next restart after 'crashes' consecutive crashes
+fn backoff(crashes: u32, policy: &RestartPolicy) -> Duration {
+    2u32.checked_pow(crashes)
+        .and_then(|factor| policy.initial_backoff.checked_mul(factor))
+        .map_or(policy.max_backoff, |backoff| {
+            backoff.min(policy.max_backoff)
+        })
+}
+
+/// Sleeps for 'duration' and returns false if a stop was requested in the meantime
+fn sleep_unless_stopped(duration: Duration, stop_requested: &AtomicBool) -> bool {
+    let deadline = Instant::now() + duration;
+    while Instant::now() < deadline {
+        if stop_requested.load(Ordering::SeqCst) {
+            return false;
+        }
+        thread::sleep(POLL_INTERVAL.min(deadline - Instant::now()));
+    }
+    !stop_requested.load(Ordering::SeqCst)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        path::Path,
+        process::{Child, Command, ExitStatus},
+        sync::{atomic::AtomicBool, Mutex},
+        time::{Duration, SystemTime},
+    };
+
+    use super::{
+        backoff, latest_crash_report, supervise, RestartPolicy, ServerState, Shared, Supervised,
+        CRASH_REPORTS_DIR,
+    };
+    use crate::instance::Result;
+
+    /// A shell process which stands in for a server
+    impl Supervised for Child {
+        fn try_wait(&mut self) -> Result<Option<ExitStatus>> {
+            Ok(Child::try_wait(self)?)
+        }
+
+        fn try_stop(&mut self) -> Result<bool> {
+            self.kill()?;
+            Ok(self.wait()?.success())
+        }
+    }
+
+    fn shared() -> Mutex<Shared<Child>> {
+        Mutex::new(Shared {
+            state: ServerState::Starting,
+            instance: None,
+            crash_report: None,
+            restarts: 0,
+        })
+    }
+
+    fn policy(max_restarts: usize) -> RestartPolicy {
+        RestartPolicy {
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(10),
+            stable_after: Duration::from_secs(60),
+            max_restarts,
+            window: Duration::from_secs(60),
+        }
+    }
+
+    /// Runs the supervisor with processes executing 'script' and returns the number of starts
+    fn run(script: &str, policy: &RestartPolicy, shared: &Mutex<Shared<Child>>) -> usize {
+        let mut starts = 0;
+        supervise(
+            || {
+                starts += 1;
+                Ok(Command::new("sh").args(["-c", script]).spawn()?)
+            },
+            Path::new("."),
+            policy,
+            shared,
+            &AtomicBool::new(false),
+        );
+        starts
+    }
+
+    #[test]
+    fn test_restart_until_limit() {
+        let shared = shared();
+        let starts = run("exit 1", &policy(2), &shared);
+
+        assert_eq!(starts, 3);
+        let shared = shared.lock().unwrap();
+        assert_eq!(shared.restarts, 2);
+        assert_eq!(shared.state, ServerState::Crashed);
+    }
+
+    #[test]
+    fn test_clean_exit_is_not_restarted() {
+        let shared = shared();
+        let starts = run("exit 0", &policy(2), &shared);
+
+        assert_eq!(starts, 1);
+        let shared = shared.lock().unwrap();
+        assert_eq!(shared.restarts, 0);
+        assert_eq!(shared.state, ServerState::Stopped);
+    }
+
+    #[test]
+    fn test_clean_exit_with_crash_report_is_restarted() {
+        let dir = tempfile::tempdir().unwrap();
+        let shared = shared();
+        let mut starts = 0;
+
+        supervise(
+            || {
+                starts += 1;
+                if starts == 1 {
+                    let reports = dir.path().join(CRASH_REPORTS_DIR);
+                    fs::create_dir_all(&reports).unwrap();
+                    let report = reports.join("crash-server.txt");
+                    fs::write(&report, "---- Minecraft Crash Report ----").unwrap();
+                    // The coarse file system clock may lag behind the start time
+                    fs::File::options()
+                        .write(true)
+                        .open(&report)?
+                        .set_modified(SystemTime::now())?;
+                }
+                Ok(Command::new("sh").args(["-c", "exit 0"]).spawn()?)
+            },
+            dir.path(),
+            &policy(2),
+            &shared,
+            &AtomicBool::new(false),
+        );
+
+        assert_eq!(starts, 2);
+        let shared = shared.lock().unwrap();
+        assert_eq!(shared.restarts, 1);
+        assert_eq!(shared.state, ServerState::Stopped);
+        assert_eq!(
+            shared.crash_report.as_ref().unwrap().content,
+            "---- Minecraft Crash Report ----"
+        );
+    }
+
+    #[test]
+    fn test_stop() {
+        let shared = shared();
+        let stop_requested = AtomicBool::new(false);
+
+        std::thread::scope(|scope| {
+            let supervisor = scope.spawn(|| {
+                supervise(
+                    || Ok(Command::new("sh").args(["-c", "sleep 60"]).spawn()?),
+                    Path::new("."),
+                    &policy(2),
+                    &shared,
+                    &stop_requested,
+                )
+            });
+            while shared.lock().unwrap().state != ServerState::Running {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            stop_requested.store(true, std::sync::atomic::Ordering::SeqCst);
+            supervisor.join().unwrap();
+        });
+
+        let shared = shared.lock().unwrap();
+        assert_eq!(shared.state, ServerState::Stopped);
+        assert_eq!(shared.restarts, 0);
+        assert!(shared.instance.is_none());
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RestartPolicy {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            ..RestartPolicy::default()
+        };
+
+        let delays: Vec<_> = (0..5).map(|crashes| backoff(crashes, &policy)).collect();
+        assert_eq!(delays, [1, 2, 4, 5, 5].map(Duration::from_secs).to_vec());
+        assert_eq!(backoff(u32::MAX, &policy), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_latest_crash_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let since = SystemTime::now() - Duration::from_secs(60);
+        assert!(latest_crash_report(dir.path(), since).unwrap().is_none());
+
+        let reports = dir.path().join(CRASH_REPORTS_DIR);
+        fs::create_dir(&reports).unwrap();
+        fs::write(
+            reports.join("crash-server.txt"),
+            "---- Minecraft Crash Report ----",
+        )
+        .unwrap();
+
+        let report = latest_crash_report(dir.path(), since).unwrap().unwrap();
+        assert_eq!(report.content, "---- Minecraft Crash Report ----");
+    }
+}
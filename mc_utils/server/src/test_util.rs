@@ -0,0 +1,57 @@
+//! Helpers shared by the tests of this crate
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    thread,
+};
+
+/// A minimal http server which serves fixed responses for the given paths
+///
+/// Returns the base url of the server. Unknown paths are answered with 404.
+pub fn serve(routes: Vec<(&str, Vec<u8>)>) -> String {
+    let routes: HashMap<String, Vec<u8>> = routes
+        .into_iter()
+        .map(|(path, body)| (path.to_string(), body))
+        .collect();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => break,
+            };
+
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            // Skip the headers
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+            }
+
+            let path = request_line.split(' ').nth(1).unwrap_or_default();
+            let (status, body) = match routes.get(path) {
+                Some(body) => ("200 OK", body.as_slice()),
+                None => ("404 Not Found", &b""[..]),
+            };
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(body).unwrap();
+        }
+    });
+
+    format!("http://{}", address)
+}
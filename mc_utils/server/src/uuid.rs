@@ -0,0 +1,234 @@
+//! Player uuids
+//!
+//! Servers with `online-mode=false` derive the uuid of a player from its name, see [`Uuid::offline`].
+//! Online uuids have to be requested from mojang, which is abstracted by [`UuidLookup`].
+use std::{collections::HashMap, fmt::Display, io, str::FromStr};
+
+use md5::{Digest, Md5};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::http::HttpClient;
+
+pub const PROFILE_LOOKUP_URL: &str = "https://api.mojang.com/users/profiles/minecraft/";
+
+/// A version 3 or version 4 uuid as used by minecraft
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Uuid(u128);
+
+impl Uuid {
+    pub const fn from_u128(value: u128) -> Self {
+        Uuid(value)
+    }
+
+    pub const fn as_u128(&self) -> u128 {
+        self.0
+    }
+
+    /// Computes the uuid a server in offline mode assigns to the player 'name'
+    ///
+    /// This is the name based (version 3) uuid of `OfflinePlayer:<name>`
+    pub fn offline(name: &str) -> Self {
+        let mut hash: [u8; 16] = Md5::digest(format!("OfflinePlayer:{}", name)).into();
+        // set the version to 3
+        hash[6] = (hash[6] & 0x0f) | 0x30;
+        // set the variant to IETF
+        hash[8] = (hash[8] & 0x3f) | 0x80;
+        Uuid(u128::from_be_bytes(hash))
+    }
+
+    /// Converts the uuid to the int array form used in nbt, most significant int first
+    pub fn to_int_array(&self) -> [i32; 4] {
+        [
+            (self.0 >> 96) as i32,
+            (self.0 >> 64) as i32,
+            (self.0 >> 32) as i32,
+            self.0 as i32,
+        ]
+    }
+
+    /// Converts the int array form used in nbt to a uuid
+    pub fn from_int_array(ints: [i32; 4]) -> Self {
+        Uuid(
+            ints.iter()
+                .fold(0, |acc, &int| (acc << 32) | int as u32 as u128),
+        )
+    }
+
+    /// Formats the uuid without dashes, like the mojang api does
+    pub fn simple(&self) -> String {
+        format!("{:032x}", self.0)
+    }
+}
+
+/// Formats the uuid in its dashed form, e.g. `069a79f4-44e9-4726-a5be-fca90e38aaf5`
+impl Display for Uuid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hex = self.simple();
+        write!(
+            f,
+            "{}-{}-{}-{}-{}",
+            &hex[0..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..32]
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseUuidError;
+
+impl Display for ParseUuidError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Invalid uuid")
+    }
+}
+
+impl std::error::Error for ParseUuidError {}
+
+/// Parses both the dashed and the undashed form
+impl FromStr for Uuid {
+    type Err = ParseUuidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = match s.len() {
+            32 => s.to_string(),
+            36 => {
+                let dashes_valid = [8, 13, 18, 23].iter().all(|&i| s.as_bytes()[i] == b'-');
+                if !dashes_valid {
+                    return Err(ParseUuidError);
+                }
+                s.replace('-', "")
+            }
+            _ => return Err(ParseUuidError),
+        };
+
+        // Catches additional dashes in the dashed form
+        if hex.len() != 32 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(ParseUuidError);
+        }
+        u128::from_str_radix(&hex, 16)
+            .map(Uuid)
+            .map_err(|_| ParseUuidError)
+    }
+}
+
+impl Serialize for Uuid {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        ser.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Uuid {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let uuid_str = String::deserialize(de)?;
+        uuid_str.parse().map_err(D::Error::custom)
+    }
+}
+
+/// Resolves the online uuid of a player
+pub trait UuidLookup {
+    /// Returns the uuid of the player 'name' or `None` if no such player exists
+    fn lookup(&self, name: &str) -> io::Result<Option<Uuid>>;
+}
+
+/// Looks up uuids using the mojang api
+#[derive(Debug, Clone)]
+pub struct MojangUuidLookup {
+    pub url: String,
+    pub client: HttpClient,
+}
+
+impl Default for MojangUuidLookup {
+    fn default() -> Self {
+        MojangUuidLookup {
+            url: PROFILE_LOOKUP_URL.to_string(),
+            client: HttpClient::default(),
+        }
+    }
+}
+
+impl UuidLookup for MojangUuidLookup {
+    fn lookup(&self, name: &str) -> io::Result<Option<Uuid>> {
+        #[derive(Deserialize)]
+        struct Profile {
+            id: Uuid,
+        }
+
+        let response = self.client.send(&format!("{}{}", self.url, name), &[])?;
+        match response.status {
+            // The api responds with an empty body if the player does not exist
+            204 | 404 => return Ok(None),
+            200..=299 => {}
+            status => {
+                return Err(io::Error::other(format!(
+                    "Profile lookup failed with status {}",
+                    status
+                )))
+            }
+        }
+
+        let profile: Profile = serde_json::from_reader(response.body)?;
+        Ok(Some(profile.id))
+    }
+}
+
+/// A fixed table of names, which can be used to stub the mojang api
+impl UuidLookup for HashMap<String, Uuid> {
+    fn lookup(&self, name: &str) -> io::Result<Option<Uuid>> {
+        Ok(self.get(name).copied())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::{Uuid, UuidLookup};
+
+    #[test]
+    fn test_offline_uuid() {
+        assert_eq!(
+            Uuid::offline("Notch").to_string(),
+            "b50ad385-829d-3141-a216-7e7d7539ba7f"
+        );
+    }
+
+    #[test]
+    fn test_parse_uuid() {
+        let dashed: Uuid = "069a79f4-44e9-4726-a5be-fca90e38aaf5".parse().unwrap();
+        let simple: Uuid = "069a79f444e94726a5befca90e38aaf5".parse().unwrap();
+
+        assert_eq!(dashed, simple);
+        assert_eq!(simple.simple(), "069a79f444e94726a5befca90e38aaf5");
+        assert!("069a79f4-44e947-26a5-befca90e38aaf5"
+            .parse::<Uuid>()
+            .is_err());
+        assert!("069a79f4-44e9-4726-a5be-fca90e38-af5"
+            .parse::<Uuid>()
+            .is_err());
+        assert!("not a uuid".parse::<Uuid>().is_err());
+    }
+
+    #[test]
+    fn test_int_array() {
+        let uuid: Uuid = "069a79f4-44e9-4726-a5be-fca90e38aaf5".parse().unwrap();
+        let ints = uuid.to_int_array();
+
+        assert_eq!(ints, [110787060, 1156138790, -1514210135, 238594805]);
+        assert_eq!(Uuid::from_int_array(ints), uuid);
+    }
+
+    #[test]
+    fn test_stub_lookup() {
+        let uuid = Uuid::offline("Steve");
+        let mut stub = HashMap::new();
+        stub.insert("Steve".to_string(), uuid);
+
+        assert_eq!(stub.lookup("Steve").unwrap(), Some(uuid));
+        assert_eq!(stub.lookup("Alex").unwrap(), None);
+    }
+}
@@ -1,37 +1,123 @@
-use std::{cmp::Ordering, path::Path};
-use std::{fs::File, io};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::Display,
+    fs::File,
+    io::{self, BufReader, Read},
+    ops::{Bound, RangeBounds},
+    path::Path,
+};
 
 use chrono::DateTime;
-use io::copy;
 use serde::{Deserialize, Deserializer};
 
+use crate::{
+    cache::Cache,
+    details::VersionDetails,
+    download::{Checksum, DownloadError, Downloader},
+    http::HttpClient,
+    version_id::VersionId,
+};
+
 pub const VERSION_MANIFEST_URL: &str =
     "https://launchermeta.mojang.com/mc/game/version_manifest.json";
+/// The version manifest which additionally lists the sha1 hash of every version json
+pub const VERSION_MANIFEST_V2_URL: &str =
+    "https://piston-meta.mojang.com/mc/game/version_manifest_v2.json";
+
+#[derive(Debug)]
+pub enum ManifestError {
+    IoError(io::Error),
+    /// The manifest is not valid json or does not have the expected layout
+    JsonError(serde_json::Error),
+}
 
-/// Downloads a file from 'url' to the file at 'destination'
-///
-/// On success, the total number of bytes is returned
-fn download_file<U: AsRef<Path>>(url: &str, destination: U) -> io::Result<u64> {
-    let mut response = ureq::get(url)
-        .call()
-        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
-        .into_reader();
+impl From<io::Error> for ManifestError {
+    fn from(error: io::Error) -> Self {
+        ManifestError::IoError(error)
+    }
+}
+
+impl From<serde_json::Error> for ManifestError {
+    fn from(error: serde_json::Error) -> Self {
+        ManifestError::JsonError(error)
+    }
+}
+
+impl From<DownloadError> for ManifestError {
+    fn from(error: DownloadError) -> Self {
+        ManifestError::IoError(error.into())
+    }
+}
 
-    let mut out = File::create(destination.as_ref())?;
+impl From<ManifestError> for io::Error {
+    fn from(error: ManifestError) -> Self {
+        match error {
+            ManifestError::IoError(error) => error,
+            ManifestError::JsonError(error) => error.into(),
+        }
+    }
+}
 
-    copy(&mut response, &mut out)
+impl Display for ManifestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifestError::IoError(error) => write!(f, "Could not load the manifest: {}", error),
+            ManifestError::JsonError(error) => write!(f, "Malformed manifest: {}", error),
+        }
+    }
 }
 
+impl std::error::Error for ManifestError {}
+
 /// Downloads a minecraft server of the given version to `destination`
-/// Returns the location of the server jar
-pub fn download_server(version: &VersionInfo, destination: impl AsRef<Path>) -> io::Result<()> {
-    let jar_url = version.jar_url().ok_or_else(|| {
-        io::Error::new(io::ErrorKind::NotFound, "Could not find the given version")
-    })?;
-    download_file(&jar_url, destination)?;
+///
+/// The jar is verified against the sha1 hash and size published in the version json.
+pub fn download_server(
+    version: &VersionInfo,
+    destination: impl AsRef<Path>,
+) -> Result<(), DownloadError> {
+    download_server_with(version, destination, &Downloader::default())
+}
+
+/// Like [`download_server`], but uses 'downloader' to report progress, retry or cancel
+pub fn download_server_with(
+    version: &VersionInfo,
+    destination: impl AsRef<Path>,
+    downloader: &Downloader,
+) -> Result<(), DownloadError> {
+    let download = version
+        .details(downloader.client())
+        .map_err(io::Error::from)?
+        .downloads
+        .server
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "The version has no server download",
+            )
+        })?;
+    downloader.download(&download.url, destination, Some(&download.checksum()))?;
     Ok(())
 }
 
+/// Like [`download_server`], but copies the jar from 'cache' if it was downloaded before
+pub fn download_server_cached(
+    version: &VersionInfo,
+    destination: impl AsRef<Path>,
+    cache: &Cache,
+) -> Result<(), DownloadError> {
+    let download = version.server_download_cached(cache)?;
+    cache.copy_object(&download.url, &download.checksum, destination)
+}
+
+/// A file listed in the `downloads` section of a version json
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionDownload {
+    pub url: String,
+    pub checksum: Checksum,
+}
+
 #[derive(Debug, Deserialize, Eq, PartialEq, Copy, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum VersionType {
@@ -48,6 +134,15 @@ pub struct VersionInfo {
     #[serde(rename(deserialize = "type"))]
     pub typ: VersionType,
     pub url: String,
+    /// The sha1 hash of the version json, only listed in the v2 manifest
+    #[serde(default)]
+    pub sha1: Option<String>,
+    /// Only listed in the v2 manifest
+    #[serde(default, rename(deserialize = "complianceLevel"))]
+    pub compliance_level: Option<u32>,
+    /// The time of the last change to the version json
+    #[serde(default, deserialize_with = "deserialize_time")]
+    pub time: i64,
     /// The release time is used to uniquely identify a version
     #[serde(
         rename(deserialize = "releaseTime"),
@@ -57,16 +152,58 @@ pub struct VersionInfo {
 }
 
 impl VersionInfo {
+    /// Parses the name of this version
+    pub fn id(&self) -> VersionId {
+        VersionId::parse(&self.name)
+    }
+
     pub fn jar_url(&self) -> Option<String> {
-        let data: serde_json::Value = ureq::get(&self.url).call().ok()?.into_json().ok()?;
+        self.server_download().map(|download| download.url)
+    }
 
-        Some(
-            data.get("downloads")?
-                .get("server")?
-                .get("url")?
-                .as_str()?
-                .to_owned(),
-        )
+    /// Returns the url, sha1 hash and size of the server jar
+    ///
+    /// The version json is read through the default [`Cache`].
+    pub fn server_download(&self) -> Option<VersionDownload> {
+        self.server_download_cached(&Cache::default()).ok()
+    }
+
+    /// Downloads and parses the version json
+    pub fn details(&self, client: &HttpClient) -> Result<VersionDetails, ManifestError> {
+        VersionDetails::from_reader(client.get(&self.url)?)
+    }
+
+    /// Like [`VersionInfo::details`], but reads the version json from 'cache'
+    ///
+    /// Version jsons are never refreshed, since their urls contain the hash of their content.
+    /// If the manifest lists the hash, the version json is verified and stored content addressed.
+    pub fn details_cached(&self, cache: &Cache) -> Result<VersionDetails, ManifestError> {
+        match &self.sha1 {
+            Some(sha1) => {
+                let checksum = Checksum {
+                    sha1: sha1.clone(),
+                    size: None,
+                };
+                let path = cache.fetch_object(&self.url, &checksum)?;
+                VersionDetails::from_reader(BufReader::new(File::open(path)?))
+            }
+            None => VersionDetails::from_reader(cache.fetch_document(&self.url, None)?.as_slice()),
+        }
+    }
+
+    /// Like [`VersionInfo::server_download`], but reads the version json from 'cache'
+    pub fn server_download_cached(&self, cache: &Cache) -> io::Result<VersionDownload> {
+        let details = self.details_cached(cache)?;
+        details
+            .downloads
+            .server
+            .map(VersionDownload::from)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "The version has no server download",
+                )
+            })
     }
 }
 
@@ -98,18 +235,101 @@ pub struct LatestVersions {
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(from = "RawManifest")]
 pub struct VersionManifest {
     latest: LatestVersions,
     /// A sorted vector of versions, the latest version is at index 0
     versions: Vec<VersionInfo>,
+    /// Maps the version names to their index in `versions`
+    index: HashMap<String, usize>,
+}
+
+/// The manifest as it is stored, before the versions are sorted and indexed
+#[derive(Deserialize)]
+struct RawManifest {
+    latest: LatestVersions,
+    versions: Vec<VersionInfo>,
+}
+
+impl From<RawManifest> for VersionManifest {
+    fn from(raw: RawManifest) -> Self {
+        let mut versions = raw.versions;
+        // Sorts in descending order
+        versions.sort_unstable_by(|a, b| b.cmp(a));
+        let index = versions
+            .iter()
+            .enumerate()
+            .map(|(index, version)| (version.name.clone(), index))
+            .collect();
+
+        VersionManifest {
+            latest: raw.latest,
+            versions,
+            index,
+        }
+    }
 }
 
 impl VersionManifest {
-    /// Searches linearly for a version with 'name'
-    ///
-    /// Starts at the latest version
     pub fn find_version(&self, name: &str) -> Option<&VersionInfo> {
-        self.versions.iter().find(|info| info.name == name)
+        self.index.get(name).map(|index| &self.versions[*index])
+    }
+
+    /// Iterates over all versions, starting at the latest version
+    pub fn versions(&self) -> impl Iterator<Item = &VersionInfo> {
+        self.versions.iter()
+    }
+
+    /// Iterates over all versions of type 'typ', starting at the latest version
+    pub fn versions_of_type(&self, typ: VersionType) -> impl Iterator<Item = &VersionInfo> {
+        self.versions().filter(move |version| version.typ == typ)
+    }
+
+    /// Returns all versions within 'range' by release time, starting at the oldest version
+    ///
+    /// The bounds are version names, e.g. `manifest.range("1.16.5"..="1.20.4")`.
+    /// Returns `None` if one of the bounds is not a known version.
+    pub fn range<'a>(&self, range: impl RangeBounds<&'a str>) -> Option<Vec<&VersionInfo>> {
+        let start = match range.start_bound() {
+            Bound::Included(name) => Bound::Included(self.find_version(name)?.release_time),
+            Bound::Excluded(name) => Bound::Excluded(self.find_version(name)?.release_time),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(name) => Bound::Included(self.find_version(name)?.release_time),
+            Bound::Excluded(name) => Bound::Excluded(self.find_version(name)?.release_time),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+
+        Some(
+            self.versions
+                .iter()
+                .rev()
+                .filter(|version| (start, end).contains(&version.release_time))
+                .collect(),
+        )
+    }
+
+    /// Returns the release which the version 'name' leads to, or the version itself if it is a release
+    ///
+    /// Pre-releases and release candidates name their release and snapshots are looked up in
+    /// the table of [`VersionId::target_release`]. Other snapshots are assigned to the next
+    /// release by release time, which is wrong if a hotfix was released in between.
+    /// Returns `None` for unknown versions and for snapshots of unreleased versions.
+    pub fn release_for(&self, name: &str) -> Option<&VersionInfo> {
+        let version = self.find_version(name)?;
+        if version.typ == VersionType::Release {
+            return Some(version);
+        }
+
+        match version.id().target_release() {
+            Some(release) => self
+                .versions_of_type(VersionType::Release)
+                .find(|candidate| candidate.id() == VersionId::Release(release)),
+            None => self.versions.iter().rev().find(|release| {
+                release.typ == VersionType::Release && release.release_time >= version.release_time
+            }),
+        }
     }
 
     pub fn latest_release(&self) -> &str {
@@ -119,24 +339,42 @@ impl VersionManifest {
     pub fn latest_snapshot(&self) -> &str {
         &self.latest.snapshot
     }
+
+    /// Downloads the v2 manifest from the official mojang url
+    pub fn fetch(client: &HttpClient) -> Result<Self, ManifestError> {
+        VersionManifest::from_url(client, VERSION_MANIFEST_V2_URL)
+    }
+
+    /// Downloads the manifest from 'url', which can point to a mirror
+    pub fn from_url(client: &HttpClient, url: &str) -> Result<Self, ManifestError> {
+        VersionManifest::from_reader(client.get(url)?)
+    }
+
+    /// Reads the manifest from a local file
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, ManifestError> {
+        VersionManifest::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    pub fn from_reader(reader: impl Read) -> Result<Self, ManifestError> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Reads the manifest from 'cache' and downloads it again once it is older than the cache ttl
+    pub fn from_cache(cache: &Cache) -> Result<Self, ManifestError> {
+        let data = cache.fetch_document(VERSION_MANIFEST_V2_URL, Some(cache.manifest_ttl))?;
+        VersionManifest::from_reader(data.as_slice())
+    }
 }
 
+/// Loads the manifest through the default [`Cache`] and panics on failure,
+/// use [`VersionManifest::from_cache`] or [`VersionManifest::fetch`] to handle errors
 impl Default for VersionManifest {
     fn default() -> Self {
-        let mut manifest: VersionManifest = ureq::get(VERSION_MANIFEST_URL)
-            .call()
-            .expect("Could not download the version manifest")
-            .into_json()
-            .expect("Malformed response");
-
-        // Sorts in descending order
-        manifest.versions.sort_unstable_by(|a, b| b.cmp(a));
-
-        manifest
+        VersionManifest::from_cache(&Cache::default()).expect("Could not load the version manifest")
     }
 }
 
-fn deserialize_time<'de, D>(de: D) -> Result<i64, D::Error>
+pub(crate) fn deserialize_time<'de, D>(de: D) -> Result<i64, D::Error>
 where
     D: Deserializer<'de>,
 {
@@ -150,7 +388,8 @@ where
 
 #[cfg(test)]
 mod test {
-    use super::{download_file, VersionManifest};
+    use super::{download_server_cached, ManifestError, VersionInfo, VersionManifest, VersionType};
+    use crate::{cache::Cache, download::download_file, http::HttpClient, test_util::serve};
 
     #[test]
     fn test_version_manifest() {
@@ -186,10 +425,167 @@ mod test {
             .unwrap()
             .jar_url()
             .unwrap();
-        let result = download_file(url.as_str(), "server.jar");
+        let result = download_file(&HttpClient::default(), url.as_str(), "server.jar");
 
         assert!(result.is_ok());
 
         std::fs::remove_file("server.jar").expect("Could not remove file");
     }
+
+    #[test]
+    fn test_download_server_cached() {
+        let url = serve(vec![
+            (
+                "/1.20.4.json",
+                br#"{"id": "1.20.4", "type": "release", "releaseTime": "2023-12-07T12:56:20+00:00", "downloads": {"server": {"url": "SERVER/server.jar", "sha1": "2aae6c35c94fcfb415dbe95f408b9ce91ee846ed", "size": 11}}}"#.to_vec(),
+            ),
+            ("/server.jar", b"hello world".to_vec()),
+        ]);
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(cache_dir.path());
+        let version = VersionInfo {
+            name: "1.20.4".to_string(),
+            typ: VersionType::Release,
+            url: format!("{}/1.20.4.json", url),
+            sha1: None,
+            compliance_level: None,
+            time: 0,
+            release_time: 0,
+        };
+        // The stub server address is not known in advance, so it is patched into the url
+        let mut download = version.server_download_cached(&cache).unwrap();
+        download.url = download.url.replace("SERVER", &url);
+        assert_eq!(download.checksum.size, Some(11));
+
+        let dir = tempfile::tempdir().unwrap();
+        cache
+            .copy_object(&download.url, &download.checksum, dir.path().join("a.jar"))
+            .unwrap();
+
+        // Everything is cached now
+        let offline = Cache::new(cache_dir.path()).offline(true);
+        download_server_cached(&version, dir.path().join("b.jar"), &offline).unwrap();
+        assert_eq!(
+            std::fs::read(dir.path().join("b.jar")).unwrap(),
+            b"hello world"
+        );
+    }
+
+    #[test]
+    fn test_manifest_from_reader() {
+        let manifest = VersionManifest::from_reader(
+            &br#"{
+                "latest": {"release": "1.20.4", "snapshot": "24w03a"},
+                "versions": [
+                    {"id": "1.20.4", "type": "release", "url": "a", "releaseTime": "2023-12-07T12:56:20+00:00"},
+                    {"id": "24w03a", "type": "snapshot", "url": "b", "time": "2024-01-17T13:17:13+00:00", "releaseTime": "2024-01-17T13:12:13+00:00", "sha1": "abc", "complianceLevel": 1}
+                ]
+            }"#[..],
+        )
+        .unwrap();
+
+        assert_eq!(manifest.latest_release(), "1.20.4");
+        let snapshot = manifest.find_version("24w03a").unwrap();
+        assert_eq!(snapshot.url, "b");
+        assert_eq!(snapshot.sha1.as_deref(), Some("abc"));
+        assert_eq!(snapshot.compliance_level, Some(1));
+        assert_eq!(manifest.find_version("1.20.4").unwrap().sha1, None);
+        // The latest version comes first
+        assert_eq!(manifest.versions[0].name, "24w03a");
+        assert_eq!(manifest.release_for("24w03a"), None);
+        assert_eq!(
+            manifest.release_for("1.20.4").unwrap().name,
+            manifest.latest_release()
+        );
+
+        assert!(matches!(
+            VersionManifest::from_reader(&b"{}"[..]),
+            Err(ManifestError::JsonError(_))
+        ));
+        assert!(matches!(
+            VersionManifest::from_path("does/not/exist.json"),
+            Err(ManifestError::IoError(_))
+        ));
+    }
+
+    #[test]
+    fn test_query_versions() {
+        let manifest = VersionManifest::from_reader(
+            &br#"{
+                "latest": {"release": "1.20.4", "snapshot": "1.20.4"},
+                "versions": [
+                    {"id": "1.20.4", "type": "release", "url": "", "releaseTime": "2023-12-07T12:56:20+00:00"},
+                    {"id": "1.20.4-rc1", "type": "snapshot", "url": "", "releaseTime": "2023-12-05T12:56:20+00:00"},
+                    {"id": "1.20.3", "type": "release", "url": "", "releaseTime": "2023-12-04T12:56:20+00:00"},
+                    {"id": "1.20.2", "type": "release", "url": "", "releaseTime": "2023-09-20T09:02:57+00:00"},
+                    {"id": "23w31a", "type": "snapshot", "url": "", "releaseTime": "2023-08-02T12:56:20+00:00"},
+                    {"id": "1.20.1", "type": "release", "url": "", "releaseTime": "2023-06-12T12:56:20+00:00"}
+                ]
+            }"#[..],
+        )
+        .unwrap();
+
+        let names = |versions: Vec<&VersionInfo>| -> Vec<String> {
+            versions
+                .into_iter()
+                .map(|version| version.name.clone())
+                .collect()
+        };
+        assert_eq!(
+            names(manifest.range("23w31a"..="1.20.4").unwrap()),
+            ["23w31a", "1.20.2", "1.20.3", "1.20.4-rc1", "1.20.4"]
+        );
+        assert_eq!(
+            names(manifest.range(.."1.20.3").unwrap()),
+            ["1.20.1", "23w31a", "1.20.2"]
+        );
+        assert!(manifest.range("1.0"..).is_none());
+
+        assert_eq!(manifest.versions_of_type(VersionType::Release).count(), 4);
+        assert_eq!(manifest.release_for("23w31a").unwrap().name, "1.20.2");
+        assert_eq!(manifest.release_for("1.20.4-rc1").unwrap().name, "1.20.4");
+    }
+
+    #[test]
+    fn test_release_for_interleaved_versions() {
+        // 1.16.5 was released during the snapshots of 1.17
+        let manifest = VersionManifest::from_reader(
+            &br#"{
+                "latest": {"release": "1.17", "snapshot": "1.17"},
+                "versions": [
+                    {"id": "1.17", "type": "release", "url": "", "releaseTime": "2021-06-08T11:00:40+00:00"},
+                    {"id": "1.17-pre1", "type": "snapshot", "url": "", "releaseTime": "2021-05-27T09:39:21+00:00"},
+                    {"id": "21w03a", "type": "snapshot", "url": "", "releaseTime": "2021-01-20T14:06:28+00:00"},
+                    {"id": "1.16.5", "type": "release", "url": "", "releaseTime": "2021-01-14T16:05:32+00:00"},
+                    {"id": "1.16.5-rc1", "type": "snapshot", "url": "", "releaseTime": "2021-01-13T15:58:41+00:00"},
+                    {"id": "20w51a", "type": "snapshot", "url": "", "releaseTime": "2020-12-16T16:47:44+00:00"},
+                    {"id": "1.16.4", "type": "release", "url": "", "releaseTime": "2020-10-29T15:49:37+00:00"}
+                ]
+            }"#[..],
+        )
+        .unwrap();
+
+        assert_eq!(manifest.release_for("20w51a").unwrap().name, "1.17");
+        assert_eq!(manifest.release_for("21w03a").unwrap().name, "1.17");
+        assert_eq!(manifest.release_for("1.16.5-rc1").unwrap().name, "1.16.5");
+        assert_eq!(manifest.release_for("1.17-pre1").unwrap().name, "1.17");
+        assert_eq!(manifest.release_for("1.16.4").unwrap().name, "1.16.4");
+    }
+
+    #[test]
+    fn test_deserialize_manifest() {
+        let manifest: VersionManifest = serde_json::from_str(
+            r#"{
+                "latest": {"release": "1.20.4", "snapshot": "1.20.4"},
+                "versions": [
+                    {"id": "1.20.3", "type": "release", "url": "", "releaseTime": "2023-12-04T12:56:20+00:00"},
+                    {"id": "1.20.4", "type": "release", "url": "", "releaseTime": "2023-12-07T12:56:20+00:00"}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.versions[0].name, "1.20.4");
+        assert_eq!(manifest.find_version("1.20.3").unwrap().name, "1.20.3");
+    }
 }
@@ -0,0 +1,263 @@
+//! Parsing and offline comparison of minecraft version names
+use std::cmp::Ordering;
+
+/// A parsed version name like `1.20.4`, `1.20.4-pre1`, `1.20.4-rc1` or `23w31a`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionId {
+    Release(Release),
+    PreRelease {
+        release: Release,
+        number: u32,
+    },
+    ReleaseCandidate {
+        release: Release,
+        number: u32,
+    },
+    Snapshot {
+        year: u32,
+        week: u32,
+        letter: char,
+    },
+    /// Any other name, like `b1.7.3` or april fools versions
+    Other(String),
+}
+
+/// The number of a release, `1.21` has the patch version 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Release {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+/// The year and week of a snapshot
+type SnapshotWeek = (u32, u32);
+
+/// The snapshots which lead to a release, as (first snapshot, last snapshot, release)
+///
+/// Used to compare snapshots with releases without the version manifest.
+const SNAPSHOT_RELEASES: &[(SnapshotWeek, SnapshotWeek, &str)] = &[
+    ((17, 43), (18, 22), "1.13"),
+    ((18, 30), (18, 33), "1.13.1"),
+    ((18, 43), (19, 14), "1.14"),
+    ((19, 34), (19, 46), "1.15"),
+    ((20, 6), (20, 22), "1.16"),
+    ((20, 27), (20, 30), "1.16.2"),
+    ((20, 45), (21, 20), "1.17"),
+    ((21, 37), (21, 44), "1.18"),
+    ((22, 3), (22, 7), "1.18.2"),
+    ((22, 11), (22, 19), "1.19"),
+    ((22, 24), (22, 24), "1.19.1"),
+    ((22, 42), (22, 46), "1.19.3"),
+    ((23, 3), (23, 7), "1.19.4"),
+    ((23, 12), (23, 18), "1.20"),
+    ((23, 31), (23, 35), "1.20.2"),
+    ((23, 40), (23, 46), "1.20.3"),
+    ((24, 3), (24, 14), "1.20.5"),
+    ((24, 18), (24, 21), "1.21"),
+    ((24, 33), (24, 40), "1.21.2"),
+    ((24, 44), (24, 46), "1.21.4"),
+    ((25, 2), (25, 10), "1.21.5"),
+    ((25, 15), (25, 21), "1.21.6"),
+];
+
+impl VersionId {
+    pub fn parse(name: &str) -> VersionId {
+        parse_snapshot(name)
+            .or_else(|| parse_pre_release(name))
+            .or_else(|| Release::parse(name).map(VersionId::Release))
+            .unwrap_or_else(|| VersionId::Other(name.to_string()))
+    }
+
+    pub fn is_release(&self) -> bool {
+        matches!(self, VersionId::Release(_))
+    }
+
+    /// Returns the release this version leads to
+    ///
+    /// Snapshots are only known up to the end of the built-in table,
+    /// use [`crate::VersionManifest::release_for`] for newer snapshots.
+    pub fn target_release(&self) -> Option<Release> {
+        match self {
+            VersionId::Release(release)
+            | VersionId::PreRelease { release, .. }
+            | VersionId::ReleaseCandidate { release, .. } => Some(*release),
+            VersionId::Snapshot { year, week, .. } => SNAPSHOT_RELEASES
+                .iter()
+                .find(|(first, last, _)| (*first..=*last).contains(&(*year, *week)))
+                .and_then(|(_, _, release)| Release::parse(release)),
+            VersionId::Other(_) => None,
+        }
+    }
+
+    /// Orders the versions of one release cycle: snapshots, pre-releases, release candidates, the release
+    fn stage(&self) -> (u32, u32) {
+        match self {
+            VersionId::Snapshot { .. } | VersionId::Other(_) => (0, 0),
+            VersionId::PreRelease { number, .. } => (1, *number),
+            VersionId::ReleaseCandidate { number, .. } => (2, *number),
+            VersionId::Release(_) => (3, 0),
+        }
+    }
+}
+
+/// Versions are compared by their names, so unknown names and snapshots
+/// outside of the built-in table can not be compared to releases
+impl PartialOrd for VersionId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if let (
+            VersionId::Snapshot { year, week, letter },
+            VersionId::Snapshot {
+                year: other_year,
+                week: other_week,
+                letter: other_letter,
+            },
+        ) = (self, other)
+        {
+            return Some((year, week, letter).cmp(&(other_year, other_week, other_letter)));
+        }
+        if let (VersionId::Other(name), VersionId::Other(other_name)) = (self, other) {
+            return (name == other_name).then_some(Ordering::Equal);
+        }
+
+        let release = self.target_release()?;
+        let other_release = other.target_release()?;
+        Some(
+            release
+                .cmp(&other_release)
+                .then_with(|| self.stage().cmp(&other.stage())),
+        )
+    }
+}
+
+impl Release {
+    /// Parses names like `1.20.4` or `1.21`
+    pub fn parse(name: &str) -> Option<Release> {
+        let mut parts = name.split('.').map(|part| {
+            if part.is_empty() || !part.bytes().all(|byte| byte.is_ascii_digit()) {
+                None
+            } else {
+                part.parse().ok()
+            }
+        });
+
+        let major = parts.next()??;
+        let minor = parts.next()??;
+        let patch = match parts.next() {
+            Some(patch) => patch?,
+            None => 0,
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Release {
+            major,
+            minor,
+            patch,
+        })
+    }
+}
+
+/// Parses snapshot names like `23w31a`
+fn parse_snapshot(name: &str) -> Option<VersionId> {
+    let (year, rest) = name.split_once('w')?;
+    if year.len() != 2 || rest.len() != 3 {
+        return None;
+    }
+    let letter = rest.chars().last()?;
+    if !letter.is_ascii_lowercase() {
+        return None;
+    }
+
+    Some(VersionId::Snapshot {
+        year: year.parse().ok()?,
+        week: rest[..2].parse().ok()?,
+        letter,
+    })
+}
+
+/// Parses `1.20.4-pre1`, `1.20.4-rc1` and the old format `1.14 Pre-Release 2`
+fn parse_pre_release(name: &str) -> Option<VersionId> {
+    if let Some((release, number)) = name.split_once(" Pre-Release ") {
+        return Some(VersionId::PreRelease {
+            release: Release::parse(release)?,
+            number: number.parse().ok()?,
+        });
+    }
+
+    let (release, suffix) = name.split_once('-')?;
+    let release = Release::parse(release)?;
+    if let Some(number) = suffix.strip_prefix("pre") {
+        return Some(VersionId::PreRelease {
+            release,
+            number: number.parse().ok()?,
+        });
+    }
+    let number = suffix.strip_prefix("rc")?.parse().ok()?;
+    Some(VersionId::ReleaseCandidate { release, number })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Release, VersionId};
+
+    #[test]
+    fn test_parse_version_id() {
+        let release = Release {
+            major: 1,
+            minor: 20,
+            patch: 4,
+        };
+        assert_eq!(VersionId::parse("1.20.4"), VersionId::Release(release));
+        assert_eq!(
+            VersionId::parse("1.20.4-pre2"),
+            VersionId::PreRelease { release, number: 2 }
+        );
+        assert_eq!(
+            VersionId::parse("1.20.4-rc1"),
+            VersionId::ReleaseCandidate { release, number: 1 }
+        );
+        assert!(matches!(
+            VersionId::parse("1.14 Pre-Release 2"),
+            VersionId::PreRelease { number: 2, .. }
+        ));
+        assert_eq!(
+            VersionId::parse("23w31a"),
+            VersionId::Snapshot {
+                year: 23,
+                week: 31,
+                letter: 'a'
+            }
+        );
+        assert!(matches!(
+            VersionId::parse("24w14potato"),
+            VersionId::Other(_)
+        ));
+        assert!(matches!(VersionId::parse("b1.7.3"), VersionId::Other(_)));
+    }
+
+    #[test]
+    fn test_compare_version_ids() {
+        let ordered = [
+            "1.16.5",
+            "23w31a",
+            "1.20.2-pre1",
+            "1.20.2-rc2",
+            "1.20.2",
+            "1.20.4",
+        ];
+        for pair in ordered.windows(2) {
+            assert!(
+                VersionId::parse(pair[0]) < VersionId::parse(pair[1]),
+                "{:?}",
+                pair
+            );
+        }
+
+        assert_eq!(
+            VersionId::parse("1.21").partial_cmp(&VersionId::parse("b1.7.3")),
+            None
+        );
+        assert!(VersionId::parse("23w31a") < VersionId::parse("23w31b"));
+    }
+}
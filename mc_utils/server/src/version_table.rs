@@ -0,0 +1,246 @@
+//! Maps version names to protocol versions, data versions and pack formats
+//!
+//! The numbers are read from the `version.json` embedded in the server jars, so a table
+//! is built once from the downloaded jars and then persisted as json.
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache::Cache,
+    jar::{JarFile, JarVersion},
+    version::VersionInfo,
+    world::read_data_version,
+};
+
+/// The numbers of a single version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionEntry {
+    pub name: String,
+    pub protocol_version: u32,
+    /// The `DataVersion` written to worlds
+    pub data_version: u32,
+    pub resource_pack_format: u32,
+    pub data_pack_format: u32,
+    pub stable: bool,
+    /// The release time as unix timestamp, if known from the manifest
+    pub release_time: Option<i64>,
+}
+
+impl VersionEntry {
+    pub fn from_jar_version(version: &JarVersion, release_time: Option<i64>) -> Self {
+        VersionEntry {
+            name: version.id.clone(),
+            protocol_version: version.protocol_version,
+            data_version: version.world_version,
+            resource_pack_format: version.pack_version.resource,
+            data_pack_format: version.pack_version.data,
+            stable: version.stable,
+            release_time,
+        }
+    }
+}
+
+/// A table of versions, ordered by their data version
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct VersionTable {
+    entries: Vec<VersionEntry>,
+}
+
+impl VersionTable {
+    pub fn new() -> Self {
+        VersionTable::default()
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut table: VersionTable = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        table.sort();
+        Ok(table)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, &self.entries)?;
+        writer.flush()
+    }
+
+    pub fn entries(&self) -> &[VersionEntry] {
+        &self.entries
+    }
+
+    /// Adds 'entry', replacing an existing entry with the same name
+    pub fn insert(&mut self, entry: VersionEntry) {
+        self.entries.retain(|existing| existing.name != entry.name);
+        self.entries.push(entry);
+        self.sort();
+    }
+
+    /// Adds the versions which are not in the table yet, reading their server jars from 'cache'
+    ///
+    /// Pass e.g. [`VersionManifest::versions`](crate::VersionManifest::versions) to build
+    /// a complete table. Versions without a server or without a `version.json`, which
+    /// exists since 1.14, are skipped. Returns the number of added versions.
+    pub fn add_versions<'a>(
+        &mut self,
+        versions: impl IntoIterator<Item = &'a VersionInfo>,
+        cache: &Cache,
+    ) -> io::Result<usize> {
+        let mut added = 0;
+        for version in versions {
+            if self.by_name(&version.name).is_some() {
+                continue;
+            }
+
+            let download = match version.server_download_cached(cache) {
+                Ok(download) => download,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(error),
+            };
+            let jar = cache.fetch_object(&download.url, &download.checksum)?;
+            let jar_version = match JarFile::open(jar)?.version_json() {
+                Ok(jar_version) => jar_version,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(error),
+            };
+
+            self.entries.push(VersionEntry {
+                // Lookups use the names of the manifest
+                name: version.name.clone(),
+                ..VersionEntry::from_jar_version(&jar_version, Some(version.release_time))
+            });
+            added += 1;
+        }
+        self.sort();
+        Ok(added)
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&VersionEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    pub fn by_data_version(&self, data_version: u32) -> Option<&VersionEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.data_version == data_version)
+    }
+
+    /// All versions which use 'protocol', e.g. 1.20.3 and 1.20.4 share 765
+    pub fn by_protocol_version(&self, protocol: u32) -> Vec<&VersionEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.protocol_version == protocol)
+            .collect()
+    }
+
+    pub fn by_resource_pack_format(&self, format: u32) -> Vec<&VersionEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.resource_pack_format == format)
+            .collect()
+    }
+
+    pub fn by_data_pack_format(&self, format: u32) -> Vec<&VersionEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.data_pack_format == format)
+            .collect()
+    }
+
+    /// The version which last saved the world at 'world_dir', according to its `level.dat`
+    pub fn world_version(&self, world_dir: impl AsRef<Path>) -> io::Result<Option<&VersionEntry>> {
+        Ok(read_data_version(world_dir)?.and_then(|version| self.by_data_version(version)))
+    }
+
+    fn sort(&mut self) {
+        self.entries
+            .sort_by_key(|entry| (entry.data_version, entry.release_time));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Write};
+
+    use sha1::{Digest, Sha1};
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use super::VersionTable;
+    use crate::{
+        cache::Cache,
+        download::hex,
+        http::{HttpClient, StubBackend},
+        version::VersionManifest,
+    };
+
+    fn server_jar(version_json: &str) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("version.json", SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(version_json.as_bytes()).unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn details(name: &str, jar: &[u8]) -> String {
+        format!(
+            r#"{{"id": "{name}", "type": "release", "releaseTime": "2023-12-07T12:56:20+00:00", "downloads": {{"server": {{"url": "http://stub/{name}.jar", "sha1": "{}", "size": {}}}}}}}"#,
+            hex(&Sha1::digest(jar)),
+            jar.len()
+        )
+    }
+
+    #[test]
+    fn test_version_table() {
+        let jar_1_20_3 = server_jar(
+            r#"{"id": "1.20.3", "name": "1.20.3", "world_version": 3698, "protocol_version": 765, "pack_version": {"resource": 22, "data": 26}, "stable": true}"#,
+        );
+        let jar_1_20_4 = server_jar(
+            r#"{"id": "1.20.4", "name": "1.20.4", "world_version": 3700, "protocol_version": 765, "pack_version": {"resource": 22, "data": 26}, "stable": true}"#,
+        );
+        let manifest = VersionManifest::from_reader(
+            &br#"{
+                "latest": {"release": "1.20.4", "snapshot": "1.20.4"},
+                "versions": [
+                    {"id": "1.20.4", "type": "release", "url": "http://stub/1.20.4.json", "releaseTime": "2023-12-07T12:56:20+00:00"},
+                    {"id": "1.20.3", "type": "release", "url": "http://stub/1.20.3.json", "releaseTime": "2023-12-05T12:10:32+00:00"},
+                    {"id": "c0.0.11a", "type": "old_alpha", "url": "http://stub/c0.0.11a.json", "releaseTime": "2009-05-17T00:00:00+00:00"}
+                ]
+            }"#[..],
+        )
+        .unwrap();
+        let backend = StubBackend::new()
+            .route("http://stub/1.20.3.json", details("1.20.3", &jar_1_20_3))
+            .route("http://stub/1.20.3.jar", jar_1_20_3)
+            .route("http://stub/1.20.4.json", details("1.20.4", &jar_1_20_4))
+            .route("http://stub/1.20.4.jar", jar_1_20_4)
+            .route(
+                "http://stub/c0.0.11a.json",
+                r#"{"id": "c0.0.11a", "type": "old_alpha", "releaseTime": "2009-05-17T00:00:00+00:00", "downloads": {}}"#,
+            );
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(dir.path().join("cache")).client(HttpClient::with_backend(backend));
+
+        let mut table = VersionTable::new();
+        assert_eq!(table.add_versions(manifest.versions(), &cache).unwrap(), 2);
+        assert_eq!(table.add_versions(manifest.versions(), &cache).unwrap(), 0);
+
+        assert_eq!(table.by_name("1.20.4").unwrap().data_version, 3700);
+        assert_eq!(table.by_data_version(3698).unwrap().name, "1.20.3");
+        let names: Vec<_> = table
+            .by_protocol_version(765)
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect();
+        assert_eq!(names, ["1.20.3", "1.20.4"]);
+        assert_eq!(table.by_data_pack_format(26).len(), 2);
+        assert!(table.by_resource_pack_format(15).is_empty());
+
+        let path = dir.path().join("versions.json");
+        table.save(&path).unwrap();
+        assert_eq!(VersionTable::from_path(&path).unwrap(), table);
+    }
+}
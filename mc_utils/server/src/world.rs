@@ -0,0 +1,243 @@
+//! Helpers for setting up the world directory of a server
+use std::{
+    convert::TryFrom,
+    fs::{self, File},
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+use flate2::read::GzDecoder;
+
+use crate::backup::{extract_archive, replace_dir};
+
+const TAG_END: u8 = 0;
+const TAG_INT: u8 = 3;
+const TAG_COMPOUND: u8 = 10;
+
+/// Replaces the world directory 'world_dir' with a copy of 'template'
+///
+/// The template can either be a world directory or a compressed tar archive of one,
+/// like the archives created by [`Backups`](crate::Backups).
+/// The existing world is kept if the template cannot be installed.
+pub fn install_world_template(
+    template: impl AsRef<Path>,
+    world_dir: impl AsRef<Path>,
+) -> io::Result<()> {
+    let template = template.as_ref();
+
+    replace_dir(world_dir.as_ref(), |new_dir| {
+        if template.is_dir() {
+            copy_dir_all(template, new_dir)
+        } else {
+            extract_archive(template, new_dir)
+        }
+    })
+}
+
+/// Reads the `DataVersion` from the `level.dat` of the world at 'world_dir'
+///
+/// The data version identifies the game version which last saved the world.
+/// Returns None for worlds older than 1.9, which have no data version.
+pub fn read_data_version(world_dir: impl AsRef<Path>) -> io::Result<Option<u32>> {
+    let file = File::open(world_dir.as_ref().join("level.dat"))?;
+    read_level_data_version(GzDecoder::new(BufReader::new(file)))
+}
+
+/// Finds `Data.DataVersion` in the uncompressed nbt of a `level.dat`
+fn read_level_data_version(mut reader: impl Read) -> io::Result<Option<u32>> {
+    if read_u8(&mut reader)? != TAG_COMPOUND {
+        return Err(invalid_nbt("The root tag is not a compound"));
+    }
+    read_nbt_string(&mut reader)?;
+
+    if !find_nbt_tag(&mut reader, TAG_COMPOUND, "Data")?
+        || !find_nbt_tag(&mut reader, TAG_INT, "DataVersion")?
+    {
+        return Ok(None);
+    }
+
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::try_from(i32::from_be_bytes(bytes)).ok())
+}
+
+/// Skips the tags of the current compound until the tag 'name' of type 'tag' is found
+///
+/// Afterwards the reader is positioned at the payload of the found tag.
+fn find_nbt_tag(reader: &mut impl Read, tag: u8, name: &str) -> io::Result<bool> {
+    loop {
+        let current = read_u8(reader)?;
+        if current == TAG_END {
+            return Ok(false);
+        }
+        if read_nbt_string(reader)? == name && current == tag {
+            return Ok(true);
+        }
+        skip_nbt_payload(reader, current)?;
+    }
+}
+
+fn skip_nbt_payload(reader: &mut impl Read, tag: u8) -> io::Result<()> {
+    match tag {
+        1 => skip(reader, 1),
+        2 => skip(reader, 2),
+        3 | 5 => skip(reader, 4),
+        4 | 6 => skip(reader, 8),
+        7 => {
+            let len = read_length(reader)?;
+            skip(reader, len)
+        }
+        8 => read_nbt_string(reader).map(drop),
+        9 => {
+            let element = read_u8(reader)?;
+            for _ in 0..read_length(reader)? {
+                skip_nbt_payload(reader, element)?;
+            }
+            Ok(())
+        }
+        TAG_COMPOUND => loop {
+            let current = read_u8(reader)?;
+            if current == TAG_END {
+                return Ok(());
+            }
+            read_nbt_string(reader)?;
+            skip_nbt_payload(reader, current)?;
+        },
+        11 => {
+            let len = read_length(reader)?;
+            skip(reader, len * 4)
+        }
+        12 => {
+            let len = read_length(reader)?;
+            skip(reader, len * 8)
+        }
+        _ => Err(invalid_nbt(format!("Unknown tag type {}", tag))),
+    }
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+/// Reads the length of an array or list, negative lengths count as empty
+fn read_length(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(i32::from_be_bytes(bytes).max(0) as u64)
+}
+
+/// Reads a string, which is modified utf-8 but lossy utf-8 suffices for tag names
+fn read_nbt_string(reader: &mut impl Read) -> io::Result<String> {
+    let mut len = [0; 2];
+    reader.read_exact(&mut len)?;
+    let mut bytes = vec![0; u16::from_be_bytes(len) as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn skip(reader: &mut impl Read, len: u64) -> io::Result<()> {
+    let skipped = io::copy(&mut reader.take(len), &mut io::sink())?;
+    if skipped < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+fn invalid_nbt(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Recursively copies the directory 'from' to 'to'
+pub(crate) fn copy_dir_all(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_all(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, io::Write};
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::{install_world_template, read_data_version};
+    use crate::backup::write_archive;
+
+    fn nbt_name(tag: u8, name: &str) -> Vec<u8> {
+        let mut bytes = vec![tag];
+        bytes.extend((name.len() as u16).to_be_bytes());
+        bytes.extend(name.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_install_world_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let template = dir.path().join("template");
+        fs::create_dir_all(template.join("datapacks")).unwrap();
+        fs::write(template.join("level.dat"), b"template").unwrap();
+
+        let world = dir.path().join("world");
+        fs::create_dir_all(&world).unwrap();
+        fs::write(world.join("stale.dat"), b"stale").unwrap();
+
+        install_world_template(&template, &world).unwrap();
+        assert_eq!(fs::read(world.join("level.dat")).unwrap(), b"template");
+        assert!(world.join("datapacks").is_dir());
+        assert!(!world.join("stale.dat").exists());
+
+        let archive = dir.path().join("template.tar.gz");
+        write_archive(&template, fs::File::create(&archive).unwrap()).unwrap();
+        fs::write(world.join("level.dat"), b"changed").unwrap();
+
+        install_world_template(&archive, &world).unwrap();
+        assert_eq!(fs::read(world.join("level.dat")).unwrap(), b"template");
+
+        // A missing template keeps the existing world
+        fs::write(world.join("level.dat"), b"changed").unwrap();
+        assert!(install_world_template(dir.path().join("missing.tar.gz"), &world).is_err());
+        assert_eq!(fs::read(world.join("level.dat")).unwrap(), b"changed");
+    }
+
+    #[test]
+    fn test_read_data_version() {
+        let mut nbt = nbt_name(10, "");
+        nbt.extend(nbt_name(10, "Data"));
+        // Tags before the data version have to be skipped
+        nbt.extend(nbt_name(8, "LevelName"));
+        nbt.extend(5u16.to_be_bytes());
+        nbt.extend(b"world");
+        nbt.extend(nbt_name(9, "ServerBrands"));
+        nbt.extend([8]);
+        nbt.extend(1i32.to_be_bytes());
+        nbt.extend(7u16.to_be_bytes());
+        nbt.extend(b"vanilla");
+        nbt.extend(nbt_name(10, "Version"));
+        nbt.extend(nbt_name(3, "Id"));
+        nbt.extend(3700i32.to_be_bytes());
+        nbt.extend([0]);
+        nbt.extend(nbt_name(12, "WanderingTraderId"));
+        nbt.extend(2i32.to_be_bytes());
+        nbt.extend([0; 16]);
+        nbt.extend(nbt_name(3, "DataVersion"));
+        nbt.extend(3700i32.to_be_bytes());
+        nbt.extend([0, 0]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&nbt).unwrap();
+        fs::write(dir.path().join("level.dat"), encoder.finish().unwrap()).unwrap();
+
+        assert_eq!(read_data_version(dir.path()).unwrap(), Some(3700));
+    }
+}
@@ -1,5 +1,7 @@
 use std::{env, path::PathBuf};
 
+use server::{AssetDownloader, AssetIndexFile, Cache, DownloadError, VersionDetails};
+
 pub use data_generator;
 pub use rcon;
 pub use server;
@@ -34,7 +36,7 @@ pub fn minecraft_dir() -> PathBuf {
         let mut dir = env::var("APPDATA").map_or(user_home, PathBuf::from);
         dir.push(".minecraft/");
         dir
-    } else if cfg!(macos) {
+    } else if cfg!(target_os = "macos") {
         user_home.push("Library/Application Support/minecraft");
         user_home
     } else {
@@ -42,3 +44,23 @@ pub fn minecraft_dir() -> PathBuf {
         user_home
     }
 }
+
+/// Returns the assets directory of the .minecraft directory, if the launcher created it
+///
+/// Its `indexes/` and `objects/` layout is the one used by [`server::AssetDownloader`],
+/// so the assets which the launcher already downloaded are reused.
+pub fn minecraft_assets_dir() -> Option<PathBuf> {
+    let assets_dir = minecraft_dir().join("assets");
+    assets_dir.is_dir().then_some(assets_dir)
+}
+
+/// The default directory for assets: [`minecraft_assets_dir`] if it exists,
+/// otherwise `assets` in the [default cache directory](Cache::default_dir)
+pub fn default_assets_dir() -> PathBuf {
+    minecraft_assets_dir().unwrap_or_else(|| Cache::default_dir().join("assets"))
+}
+
+/// Downloads the assets of the version into the [`default_assets_dir`]
+pub fn download_assets(details: &VersionDetails) -> Result<AssetIndexFile, DownloadError> {
+    AssetDownloader::default().download_assets(details, default_assets_dir())
+}